@@ -4,6 +4,8 @@ import {
   currentUser,
   fetchTeams,
   authError,
+  commandError,
+  commandWarning,
   fetchLocations,
 } from "./store";
 import { Auth } from "./components/Auth";
@@ -30,6 +32,12 @@ export function App() {
     <>
       {currentUser.value ? (
         <>
+          {commandError.value && (
+            <div style={{ color: "red" }}>{commandError.value}</div>
+          )}
+          {commandWarning.value && (
+            <div style={{ color: "orange" }}>{commandWarning.value}</div>
+          )}
           <TalkList />
           <Scheduler />
         </>
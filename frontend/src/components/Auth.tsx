@@ -8,6 +8,7 @@ export function Auth() {
   const [team, setTeam] = useState(teams.value[0] || "");
   const [attendanceMode, setAttendanceMode] = useState(AttendanceMode.OnSite);
   const [password, setPassword] = useState("");
+  const [inviteCode, setInviteCode] = useState("");
   const [isNotNao, setIsNotNao] = useState(false);
 
   const handleSubmit = (e: Event) => {
@@ -20,7 +21,13 @@ export function Auth() {
       mode === "login"
         ? { Login: { name, team, password } }
         : {
-            Register: { name, team, password, attendance_mode: attendanceMode },
+            Register: {
+              name,
+              team,
+              password,
+              attendance_mode: attendanceMode,
+              invite_code: inviteCode || null,
+            },
           },
     );
   };
@@ -62,6 +69,13 @@ export function Auth() {
 
       {mode === "register" && (
         <>
+          <input
+            placeholder="Invite Code"
+            value={inviteCode}
+            onInput={(e) => setInviteCode(e.currentTarget.value)}
+          />
+          <div class="hint">Only needed if your team got one</div>
+
           <div class="registration-checkbox">
             <input
               type="checkbox"
@@ -2,6 +2,8 @@
 import { useRef, useState, useEffect } from "preact/hooks";
 import { computed } from "@preact/signals";
 import {
+  drafts,
+  event,
   locations,
   users,
   talks as serverTalks,
@@ -9,13 +11,15 @@ import {
 } from "../store";
 import {
   isSchedulerOpen,
+  activeDraftName,
   draftTalks,
-  saveChanges,
-  discardChanges,
+  publishDraft,
+  closeScheduler,
+  deleteDraft,
+  proposeSchedule,
   updateDraftTalk,
   draggingTalkId,
   dragOffsetPx,
-  SLOT_MINUTES,
   PIXELS_PER_MINUTE,
   schedulerConfig,
 } from "../schedulerStore";
@@ -26,12 +30,14 @@ import type { Talk } from "../types";
 const MINS_PER_DAY = 24 * 60;
 const ZOOM = PIXELS_PER_MINUTE; // Alias for brevity
 
-// Pre-calculate track background styles since config/zoom are static
-const trackBackgroundStyle = (() => {
+// Track background styles, depending on the active hours and slots of the event
+function trackBackgroundStyle() {
+  const { slotMinutes, activeDayStartMin, activeDayEndMin } =
+    schedulerConfig.value;
   const dayH = MINS_PER_DAY * ZOOM;
-  const slotH = SLOT_MINUTES * ZOOM;
-  const activeStart = schedulerConfig.activeDayStartMin * ZOOM;
-  const activeEnd = schedulerConfig.activeDayEndMin * ZOOM;
+  const slotH = slotMinutes * ZOOM;
+  const activeStart = activeDayStartMin * ZOOM;
+  const activeEnd = activeDayEndMin * ZOOM;
 
   // Layer 1: Day/Night Cycle (Bottom)
   const bgDayNight = `linear-gradient(
@@ -48,7 +54,7 @@ const trackBackgroundStyle = (() => {
     backgroundSize: `100% ${slotH}px, 100% ${dayH}px`,
     backgroundRepeat: "repeat-y",
   };
-})();
+}
 
 /** Helper to snap a Y-coordinate to the nearest slot minute */
 function getSnapMinutes(
@@ -58,7 +64,8 @@ function getSnapMinutes(
 ): number {
   const relativeY = clientY - rectTop - offsetPx;
   const rawMinutes = relativeY / ZOOM;
-  return Math.round(rawMinutes / SLOT_MINUTES) * SLOT_MINUTES;
+  const { slotMinutes } = schedulerConfig.value;
+  return Math.round(rawMinutes / slotMinutes) * slotMinutes;
 }
 
 // --- Components ---
@@ -82,11 +89,28 @@ export function Scheduler() {
         <div class={styles.header}>
           <h2>Scheduler</h2>
           <div class={styles.controls}>
-            <button class={styles.cancel} onClick={discardChanges}>
-              Cancel
+            <input
+              list="scheduler-drafts"
+              placeholder="Draft"
+              value={activeDraftName.value}
+              onChange={(e) => (activeDraftName.value = e.currentTarget.value)}
+            />
+            <datalist id="scheduler-drafts">
+              {Object.keys(drafts.value).map((name) => (
+                <option key={name} value={name} />
+              ))}
+            </datalist>
+            <button class={styles.cancel} onClick={closeScheduler}>
+              Close
+            </button>
+            <button class={styles.cancel} onClick={deleteDraft}>
+              Discard Draft
             </button>
-            <button class={styles.save} onClick={saveChanges}>
-              Confirm Changes
+            <button class={styles.cancel} onClick={proposeSchedule}>
+              Propose Schedule
+            </button>
+            <button class={styles.save} onClick={publishDraft}>
+              Publish Draft
             </button>
           </div>
         </div>
@@ -183,8 +207,9 @@ function Timeline() {
   // Initial Auto-Scroll
   useEffect(() => {
     if (!scrollRef.current) return;
-    const diffMins = (currentTimeSecs.value - schedulerConfig.startEpoch) / 60;
-    const totalConfMins = schedulerConfig.daysToShow * MINS_PER_DAY;
+    const diffMins =
+      (currentTimeSecs.value - schedulerConfig.value.startEpoch) / 60;
+    const totalConfMins = schedulerConfig.value.daysToShow * MINS_PER_DAY;
 
     if (diffMins > 0 && diffMins < totalConfMins) {
       // Scroll to 2 hours before now
@@ -196,8 +221,8 @@ function Timeline() {
     Object.values(draftTalks.value).filter((t) => {
       if (!t.scheduled_at) return false;
       const start = t.scheduled_at.secs_since_epoch;
-      const rangeStart = schedulerConfig.startEpoch;
-      const rangeEnd = rangeStart + schedulerConfig.daysToShow * 86400;
+      const rangeStart = schedulerConfig.value.startEpoch;
+      const rangeEnd = rangeStart + schedulerConfig.value.daysToShow * 86400;
       return start >= rangeStart && start < rangeEnd;
     }),
   );
@@ -252,17 +277,17 @@ function Timeline() {
     updateDraftTalk(talkId, {
       location: venueId,
       scheduled_at: toSystemTime(
-        new Date((schedulerConfig.startEpoch + totalMinutes * 60) * 1000),
+        new Date((schedulerConfig.value.startEpoch + totalMinutes * 60) * 1000),
       ),
     });
     draggingTalkId.value = null;
   };
 
   // Layout Values
-  const totalHeight = schedulerConfig.daysToShow * MINS_PER_DAY * ZOOM;
-  const nowOffset = currentTimeSecs.value - schedulerConfig.startEpoch;
-  const showRedLine =
-    nowOffset >= 0 && nowOffset < schedulerConfig.daysToShow * 86400;
+  const { daysToShow, startEpoch } = schedulerConfig.value;
+  const totalHeight = daysToShow * MINS_PER_DAY * ZOOM;
+  const nowOffset = currentTimeSecs.value - startEpoch;
+  const showRedLine = nowOffset >= 0 && nowOffset < daysToShow * 86400;
 
   return (
     <div class={styles.timelineContainer}>
@@ -283,7 +308,7 @@ function Timeline() {
       <div class={styles.timelineBody} ref={scrollRef}>
         <div class={styles.timeAxis} style={{ height: `${totalHeight}px` }}>
           {Array.from({
-            length: (schedulerConfig.daysToShow * MINS_PER_DAY) / 60,
+            length: (daysToShow * MINS_PER_DAY) / 60,
           }).map((_, i) => (
             <div
               key={i}
@@ -295,10 +320,10 @@ function Timeline() {
           ))}
         </div>
 
-        <div class={styles.tracksContainer} style={trackBackgroundStyle}>
+        <div class={styles.tracksContainer} style={trackBackgroundStyle()}>
           {/* Hour Grid Lines (Rendered as DOM elements for precise alignment with Time Axis) */}
           {Array.from({
-            length: (schedulerConfig.daysToShow * MINS_PER_DAY) / 60,
+            length: (daysToShow * MINS_PER_DAY) / 60,
           }).map((_, i) => (
             <div
               key={`grid-${i}`}
@@ -308,10 +333,8 @@ function Timeline() {
           ))}
 
           {/* Day Separators */}
-          {Array.from({ length: schedulerConfig.daysToShow }).map((_, i) => {
-            const date = new Date(
-              (schedulerConfig.startEpoch + i * 86400) * 1000,
-            );
+          {Array.from({ length: daysToShow }).map((_, i) => {
+            const date = new Date((startEpoch + i * 86400) * 1000);
             return (
               <div
                 key={i}
@@ -319,6 +342,7 @@ function Timeline() {
                 style={{ top: `${i * MINS_PER_DAY * ZOOM}px` }}
               >
                 {date.toLocaleDateString("en-US", {
+                  timeZone: event.value?.timezone,
                   weekday: "long",
                   month: "short",
                   day: "numeric",
@@ -381,7 +405,8 @@ function Timeline() {
 
 function ScheduledEvent({ talk }: { talk: Talk }) {
   const startMins =
-    (talk.scheduled_at!.secs_since_epoch - schedulerConfig.startEpoch) / 60;
+    (talk.scheduled_at!.secs_since_epoch - schedulerConfig.value.startEpoch) /
+    60;
   const height = (talk.duration.secs / 60) * ZOOM;
   const top = startMins * ZOOM;
 
@@ -409,11 +434,12 @@ function ScheduledEvent({ talk }: { talk: Talk }) {
     const startHeight = height;
 
     const onMove = (evt: PointerEvent) => {
+      const { slotMinutes } = schedulerConfig.value;
       const rawH = Math.max(
-        SLOT_MINUTES * ZOOM,
+        slotMinutes * ZOOM,
         startHeight + (evt.pageY - startY),
       );
-      const snappedMins = Math.round(rawH / ZOOM / SLOT_MINUTES) * SLOT_MINUTES;
+      const snappedMins = Math.round(rawH / ZOOM / slotMinutes) * slotMinutes;
       updateDraftTalk(talk.id, {
         duration: { secs: snappedMins * 60, nanos: 0 },
       });
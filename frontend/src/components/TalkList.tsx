@@ -1,8 +1,22 @@
 import { computed } from "@preact/signals";
 import { useState } from "preact/hooks";
-import { talks, currentTimeSecs, sendCommand, currentUser } from "../store";
+import {
+  talks,
+  currentTimeSecs,
+  sendCommand,
+  currentUser,
+  logout,
+  conflicts,
+  locations,
+  users,
+} from "../store";
 import { TalkCard } from "./TalkCard";
-import { AttendanceMode, Role, type Talk } from "../types";
+import {
+  AttendanceMode,
+  Role,
+  type Conflict,
+  type Talk,
+} from "../types";
 import { openScheduler } from "../schedulerStore";
 
 export function TalkList() {
@@ -115,10 +129,26 @@ function Section({
   );
 }
 
+function describeConflict(conflict: Conflict) {
+  if ("Location" in conflict) {
+    const { location_id, talk_ids } = conflict.Location;
+    const location = locations.value[location_id]?.name ?? location_id;
+    return `talks ${talk_ids.join(" and ")} overlap at ${location}`;
+  }
+  const { user_id, talk_ids } = conflict.Nerd;
+  const name = users.value[user_id]?.name ?? user_id;
+  return `${name} is a nerd of the overlapping talks ${talk_ids.join(" and ")}`;
+}
+
 function Header() {
   const user = currentUser.value;
   const isRemote = user?.attendance_mode === AttendanceMode.Remote;
   const isScheduler = user?.roles.includes(Role.Scheduler);
+  // Schedulers see all conflicts, nerds the ones they are part of
+  const myConflicts = conflicts.value.filter(
+    (conflict) =>
+      isScheduler || ("Nerd" in conflict && conflict.Nerd.user_id === user?.id),
+  );
 
   const toggleMode = (e: Event) => {
     const checked = (e.currentTarget as HTMLInputElement).checked;
@@ -161,6 +191,13 @@ function Header() {
           </button>
         )}
 
+        {myConflicts.length > 0 && (
+          <div style={{ color: "orange" }}>
+            ⚠️ {myConflicts.length} scheduling conflicts:{" "}
+            {myConflicts.map(describeConflict).join(", ")}
+          </div>
+        )}
+
         <a
           class="calendar"
           href="#calendar"
@@ -200,6 +237,27 @@ function Header() {
             Participating remotely
           </label>
         </div>
+
+        <div style={{ fontSize: "0.9rem", display: "flex", gap: "0.5rem" }}>
+          <a
+            href="#logout"
+            onClick={(e) => {
+              e.preventDefault();
+              logout(false);
+            }}
+          >
+            Log out
+          </a>
+          <a
+            href="#logout-all"
+            onClick={(e) => {
+              e.preventDefault();
+              logout(true);
+            }}
+          >
+            Log out everywhere
+          </a>
+        </div>
       </div>
     </div>
   );
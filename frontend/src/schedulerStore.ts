@@ -1,76 +1,128 @@
-import { signal } from "@preact/signals";
-import { talks, sendCommand } from "./store";
-import type { Talk } from "./types";
+import { computed, effect, signal } from "@preact/signals";
+import {
+  talks,
+  drafts,
+  event,
+  sendCommand,
+  scheduleProposal,
+} from "./store";
+import type { Talk, TalkSchedule } from "./types";
 
-export const schedulerConfig = {
-  // The starting timestamp of the conference (00:00 of the first day)
-  // currently set to: 2025-11-23T00:00:00
-  startEpoch: new Date("2025-11-28T00:00:00").getTime() / 1000,
+/** Minutes from midnight of a "HH:MM:SS" time. */
+function minutesOfDay(time: string): number {
+  const [hours, minutes] = time.split(":").map(Number);
+  return hours * 60 + minutes;
+}
+
+// Derived from the event configured on the server
+export const schedulerConfig = computed(() => {
+  const e = event.value;
+  if (!e) {
+    return {
+      startEpoch: 0,
+      daysToShow: 0,
+      activeDayStartMin: 0,
+      activeDayEndMin: 24 * 60,
+      slotMinutes: 15,
+    };
+  }
+  return {
+    // The starting timestamp of the conference (00:00 of the first day)
+    startEpoch: e.starts_at.secs_since_epoch,
 
-  // How many days to render on the vertical timeline
-  daysToShow: 3,
+    // How many days to render on the vertical timeline
+    daysToShow:
+      (Date.parse(e.end_date) - Date.parse(e.start_date)) / 86400000 + 1,
 
-  // Day/Night Cycle for Gray Zones (Minutes from midnight)
-  activeDayStartMin: 8 * 60, // 08:00
-  activeDayEndMin: 22 * 60, // 22:00
-};
+    // Day/Night Cycle for Gray Zones (Minutes from midnight)
+    activeDayStartMin: minutesOfDay(e.active_hours_start),
+    activeDayEndMin: minutesOfDay(e.active_hours_end),
+
+    slotMinutes: e.slot_minutes,
+  };
+});
 
-export const SLOT_MINUTES = 15;
 export const PIXELS_PER_MINUTE = 1.1;
 
 export const isSchedulerOpen = signal(false);
-export const draftTalks = signal<Record<number, Talk>>({});
+// Drafts are shared with the other schedulers and kept on the server until published
+export const activeDraftName = signal("Main");
 export const draggingTalkId = signal<number | null>(null);
 export const dragOffsetPx = signal(0);
 
+/** The talks as they would be scheduled after publishing the active draft. */
+export const draftTalks = computed(() => {
+  const schedules = drafts.value[activeDraftName.value]?.talks ?? {};
+  const result: Record<number, Talk> = {};
+  Object.values(talks.value).forEach((t) => {
+    result[t.id] = { ...t, ...schedules[t.id] };
+  });
+  return result;
+});
+
 export function openScheduler() {
-  draftTalks.value = JSON.parse(JSON.stringify(talks.value));
   isSchedulerOpen.value = true;
 }
 
-export function discardChanges() {
+/** Closes the scheduler, the draft stays for later or for the other schedulers. */
+export function closeScheduler() {
   isSchedulerOpen.value = false;
-  draftTalks.value = {};
 }
 
-export function saveChanges() {
-  const original = talks.value;
-  const draft = draftTalks.value;
-
-  Object.values(draft).forEach((dTalk) => {
-    const oTalk = original[dTalk.id];
-    if (!oTalk) return;
-
-    if (dTalk.location !== oTalk.location) {
-      sendCommand({
-        UpdateLocation: { talk_id: dTalk.id, location: dTalk.location },
-      });
-    }
-
-    const dTime = dTalk.scheduled_at?.secs_since_epoch;
-    const oTime = oTalk.scheduled_at?.secs_since_epoch;
-    if (dTime !== oTime) {
-      sendCommand({
-        UpdateScheduledAt: {
-          talk_id: dTalk.id,
-          scheduled_at: dTalk.scheduled_at,
-        },
-      });
-    }
-
-    if (dTalk.duration.secs !== oTalk.duration.secs) {
-      sendCommand({
-        UpdateDuration: { talk_id: dTalk.id, duration: dTalk.duration },
-      });
-    }
-  });
+export function deleteDraft() {
+  if (drafts.value[activeDraftName.value]) {
+    sendCommand({ DeleteDraft: { name: activeDraftName.value } });
+  }
+}
 
+/** Applies the whole draft at once, so that nobody sees it half-applied. */
+export function publishDraft() {
+  if (drafts.value[activeDraftName.value]) {
+    sendCommand({ PublishDraft: { name: activeDraftName.value } });
+  }
   isSchedulerOpen.value = false;
 }
 
+/** Plans several talks in the draft with a single command. */
+export function updateDraftTalks(changes: Record<number, Partial<Talk>>) {
+  const schedules: Record<number, TalkSchedule | null> = {};
+  Object.entries(changes).forEach(([key, change]) => {
+    const id = Number(key);
+    const t = draftTalks.value[id];
+    const original = talks.value[id];
+    if (!t || !original) return;
+    const updated = { ...t, ...change };
+    const schedule: TalkSchedule = {
+      scheduled_at: updated.scheduled_at,
+      duration: updated.duration,
+      location: updated.location,
+    };
+    const unchanged =
+      schedule.scheduled_at?.secs_since_epoch ===
+        original.scheduled_at?.secs_since_epoch &&
+      schedule.duration.secs === original.duration.secs &&
+      schedule.location === original.location;
+    schedules[id] = unchanged ? null : schedule;
+  });
+  if (Object.keys(schedules).length === 0) return;
+  sendCommand({
+    UpdateDraft: { name: activeDraftName.value, schedules },
+  });
+}
+
 export function updateDraftTalk(id: number, changes: Partial<Talk>) {
-  const t = draftTalks.value[id];
-  if (t) {
-    draftTalks.value = { ...draftTalks.value, [id]: { ...t, ...changes } };
-  }
+  updateDraftTalks({ [id]: changes });
+}
+
+/** Asks the server to place the unscheduled talks in the active hours of the event. */
+export function proposeSchedule() {
+  sendCommand({ ProposeSchedule: {} });
 }
+
+// Proposals only end up in the draft, the scheduler still has to publish them
+effect(() => {
+  const proposal = scheduleProposal.value;
+  if (!proposal || !isSchedulerOpen.value) return;
+  updateDraftTalks(proposal.assignments);
+  scheduleProposal.value = null;
+});
@@ -6,10 +6,18 @@ import type {
   Command,
   ServerMessage,
   Location,
+  Session,
+  Role,
+  Conflict,
+  ScheduleProposal,
+  Draft,
+  EventInfo,
 } from "./types";
 
 export const currentUser = signal<User | null>(null);
 export const users = signal<Record<number, User>>({});
+// Roles are only known for the current user, from the login and later role updates
+const currentRoles = signal<Role[]>([]);
 export const talks = signal<Record<number, Talk>>({});
 export const teams = signal<string[]>([]);
 export const locations = signal<Record<number, Location>>([]);
@@ -17,6 +25,16 @@ export const connectionStatus = signal<
   "connecting" | "connected" | "disconnected"
 >("connecting");
 export const authError = signal<string | null>(null);
+export const commandError = signal<string | null>(null);
+export const commandWarning = signal<string | null>(null);
+export const conflicts = signal<Conflict[]>([]);
+export const scheduleProposal = signal<ScheduleProposal | null>(null);
+// Drafts of the schedule by name, only received by schedulers
+export const drafts = signal<Record<string, Draft>>({});
+export const event = signal<EventInfo | null>(null);
+export const sessions = signal<Session[]>([]);
+// Reset token issued by an admin, to be handed to the user who forgot their password
+export const passwordResetToken = signal<string | null>(null);
 
 export const currentTimeSecs = signal(Math.floor(Date.now() / 1000));
 setInterval(() => {
@@ -26,6 +44,9 @@ setInterval(() => {
 let socket: WebSocket | null = null;
 let pendingAuthCommand: AuthCommand | null = null;
 let reconnectTimeout: number | undefined;
+let nextRequestId = 0;
+// Sequence number of the last update received, to only receive missed updates after a reconnect
+let lastSeq: number | null = null;
 const RECONNECT_DELAY = 5000;
 
 export function connect() {
@@ -52,7 +73,9 @@ export function connect() {
     }
 
     const token = localStorage.getItem("reloginToken");
-    if (token) {
+    if (token && lastSeq !== null) {
+      sendAuth({ Resume: { token, last_seq: lastSeq } });
+    } else if (token) {
       sendAuth({ Relogin: { token } });
     }
   };
@@ -68,12 +91,16 @@ export function connect() {
 
   socket.onmessage = (event) => {
     const msg = JSON.parse(event.data);
+    if (typeof msg.seq === "number") {
+      lastSeq = msg.seq;
+    }
     handleMessage(msg);
   };
 }
 
 export function loginOrRegister(cmd: AuthCommand) {
   localStorage.removeItem("reloginToken");
+  lastSeq = null;
   pendingAuthCommand = cmd;
   authError.value = null;
   connect();
@@ -83,9 +110,13 @@ function handleMessage(msg: ServerMessage) {
   if ("AuthenticationSuccess" in msg) {
     const { user_id, roles, token } = msg.AuthenticationSuccess;
     localStorage.setItem("reloginToken", token);
+    currentRoles.value = roles;
     effect(() => {
       if (users.value[user_id]) {
-        currentUser.value = { ...users.value[user_id], roles };
+        currentUser.value = {
+          ...users.value[user_id],
+          roles: currentRoles.value,
+        };
       }
     });
     return;
@@ -94,15 +125,82 @@ function handleMessage(msg: ServerMessage) {
   if ("AuthenticationError" in msg) {
     authError.value = msg.AuthenticationError.reason;
     localStorage.removeItem("reloginToken");
+    lastSeq = null;
     return;
   }
 
-  if ("Users" in msg) {
-    const newUsers: Record<number, User> = {};
-    Object.values(msg.Users.users).forEach((u) => {
-      newUsers[u.id] = { ...u, roles: [] }; // Reset roles as they are session-specific
+  if ("Ack" in msg) {
+    return;
+  }
+
+  if ("Error" in msg) {
+    console.error("Command failed:", msg.Error.reason);
+    commandError.value = msg.Error.reason;
+    return;
+  }
+
+  if ("Warning" in msg) {
+    commandWarning.value = msg.Warning.warning;
+    return;
+  }
+
+  if ("ScheduleProposal" in msg) {
+    scheduleProposal.value = msg.ScheduleProposal;
+    return;
+  }
+
+  if ("Sessions" in msg) {
+    sessions.value = msg.Sessions.sessions;
+    return;
+  }
+
+  if ("PasswordReset" in msg) {
+    passwordResetToken.value = msg.PasswordReset.reset_token;
+    return;
+  }
+
+  if ("Snapshot" in msg) {
+    const snapshot = msg.Snapshot;
+    users.value = collectUsers(snapshot.users);
+    talks.value = snapshot.talks;
+    locations.value = snapshot.locations;
+    teams.value = snapshot.teams;
+    conflicts.value = snapshot.conflicts;
+    drafts.value = snapshot.drafts;
+    event.value = snapshot.event;
+    return;
+  }
+
+  if ("UpdateEvent" in msg) {
+    event.value = msg.UpdateEvent.event;
+    return;
+  }
+
+  if ("UpdateDraft" in msg) {
+    const { name, draft } = msg.UpdateDraft;
+    // eslint-disable-next-line @typescript-eslint/no-unused-vars
+    const { [name]: _, ...rest } = drafts.value;
+    drafts.value = draft ? { ...rest, [name]: draft } : rest;
+    return;
+  }
+
+  if ("Reschedule" in msg) {
+    const newTalks = { ...talks.value };
+    Object.entries(msg.Reschedule.schedules).forEach(([id, schedule]) => {
+      const t = newTalks[Number(id)];
+      if (t) newTalks[t.id] = { ...t, ...schedule };
     });
-    users.value = newUsers;
+    talks.value = newTalks;
+    return;
+  }
+
+  if ("Conflicts" in msg) {
+    conflicts.value = msg.Conflicts.conflicts;
+    return;
+  }
+
+  if ("Users" in msg) {
+    users.value = collectUsers(msg.Users.users);
     return;
   }
 
@@ -119,8 +217,60 @@ function handleMessage(msg: ServerMessage) {
     return;
   }
 
+  if ("AddLocation" in msg || "ChangeLocation" in msg) {
+    const l =
+      "AddLocation" in msg
+        ? msg.AddLocation.location
+        : msg.ChangeLocation.location;
+    locations.value = { ...locations.value, [l.id]: l };
+    return;
+  }
+
+  if ("RemoveLocation" in msg) {
+    // eslint-disable-next-line @typescript-eslint/no-unused-vars
+    const { [msg.RemoveLocation.location_id]: _, ...rest } = locations.value;
+    locations.value = rest;
+    return;
+  }
+
+  if ("AddTeam" in msg) {
+    if (!teams.value.includes(msg.AddTeam.team)) {
+      teams.value = [...teams.value, msg.AddTeam.team].sort();
+    }
+    return;
+  }
+
+  if ("RemoveTeam" in msg) {
+    teams.value = teams.value.filter((team) => team !== msg.RemoveTeam.team);
+    return;
+  }
+
+  if ("RenameTeam" in msg) {
+    const { team, new_name } = msg.RenameTeam;
+    teams.value = [
+      ...teams.value.filter((t) => t !== team && t !== new_name),
+      new_name,
+    ].sort();
+    const newUsers = { ...users.value };
+    Object.values(newUsers).forEach((u) => {
+      if (u.team === team) {
+        newUsers[u.id] = { ...u, team: new_name };
+      }
+    });
+    users.value = newUsers;
+    return;
+  }
+
   // Handle distinct updates via a helper to keep this function clean
-  if ("UpdateTitle" in msg) {
+  if ("UpdateCreator" in msg) {
+    patchTalk(msg.UpdateCreator.talk_id, {
+      creator: msg.UpdateCreator.creator,
+    });
+  } else if ("UpdateHighlight" in msg) {
+    patchTalk(msg.UpdateHighlight.talk_id, {
+      highlight: msg.UpdateHighlight.highlight,
+    });
+  } else if ("UpdateTitle" in msg) {
     patchTalk(msg.UpdateTitle.talk_id, { title: msg.UpdateTitle.title });
   } else if ("UpdateDescription" in msg) {
     patchTalk(msg.UpdateDescription.talk_id, {
@@ -164,9 +314,21 @@ function handleMessage(msg: ServerMessage) {
         [user_id]: { ...users.value[user_id], attendance_mode },
       };
     }
+  } else if ("UpdateRoles" in msg) {
+    if (msg.UpdateRoles.user_id === currentUser.value?.id) {
+      currentRoles.value = msg.UpdateRoles.roles;
+    }
   }
 }
 
+function collectUsers(received: Record<string, User>) {
+  const newUsers: Record<number, User> = {};
+  Object.values(received).forEach((u) => {
+    newUsers[u.id] = { ...u, roles: [] }; // Reset roles as they are session-specific
+  });
+  return newUsers;
+}
+
 function patchTalk(id: number, changes: Partial<Talk>) {
   if (talks.value[id]) {
     talks.value = { ...talks.value, [id]: { ...talks.value[id], ...changes } };
@@ -178,7 +340,24 @@ export function sendAuth(cmd: AuthCommand) {
 }
 
 export function sendCommand(cmd: Command) {
-  socket?.send(JSON.stringify(cmd));
+  commandError.value = null;
+  commandWarning.value = null;
+  socket?.send(JSON.stringify({ ...cmd, request_id: nextRequestId++ }));
+}
+
+export function logout(everywhere: boolean) {
+  sendCommand(everywhere ? { LogoutAll: {} } : { Logout: {} });
+  localStorage.removeItem("reloginToken");
+  lastSeq = null;
+}
+
+export function listSessions() {
+  sendCommand({ ListSessions: {} });
+}
+
+export function revokeSession(session_id: string) {
+  sendCommand({ RevokeSession: { session_id } });
+  listSessions();
 }
 
 export async function fetchTeams() {
@@ -14,6 +14,7 @@ export enum ParticipationKind {
 }
 
 export enum Role {
+  Admin = "Admin",
   Editor = "Editor",
   Scheduler = "Scheduler",
 }
@@ -43,6 +44,11 @@ export interface Location {
   id: number;
   name: string;
   live_stream: null | string;
+  capacity: null | number;
+  building: null | string;
+  floor: null | string;
+  accessibility_notes: null | string;
+  map_url: null | string;
 }
 
 // WebSocket Messages
@@ -105,6 +111,61 @@ export type RemoveNoobCommand = { RemoveNoob: TalkUserPayload };
 export type AddNerdCommand = { AddNerd: TalkUserPayload };
 export type RemoveNerdCommand = { RemoveNerd: TalkUserPayload };
 export type SetAttendanceMode = { SetAttendanceMode: SetAttendanceModePayload };
+export type LogoutCommand = { Logout: Record<string, never> };
+export type LogoutAllCommand = { LogoutAll: Record<string, never> };
+export type RevokeTokensCommand = { RevokeTokens: { user_id: number } };
+export type ChangePasswordCommand = {
+  ChangePassword: { old: string; new: string };
+};
+export type IssuePasswordResetCommand = {
+  IssuePasswordReset: { user_id: number };
+};
+export type ListSessionsCommand = { ListSessions: Record<string, never> };
+export type RevokeSessionCommand = { RevokeSession: { session_id: string } };
+export type GrantRoleCommand = { GrantRole: { user_id: number; role: Role } };
+export type RevokeRoleCommand = { RevokeRole: { user_id: number; role: Role } };
+export type CreateTeamCommand = { CreateTeam: { team: string } };
+export type RenameTeamCommand = {
+  RenameTeam: { team: string; new_name: string };
+};
+export type MergeTeamsCommand = { MergeTeams: { team: string; into: string } };
+export type DeleteTeamCommand = { DeleteTeam: { team: string } };
+export type CreateLocationCommand = { CreateLocation: Omit<Location, "id"> };
+export type EditLocationCommand = { EditLocation: { location: Location } };
+export type DeleteLocationCommand = { DeleteLocation: { location_id: number } };
+export type ProposeScheduleCommand = {
+  ProposeSchedule: Record<string, never>;
+};
+export interface TalkSchedule {
+  scheduled_at: SystemTime | null;
+  duration: Duration;
+  location: number | null;
+}
+export interface Draft {
+  talks: Record<string, TalkSchedule>;
+  based_on: Record<string, TalkSchedule>;
+  updated_by: number;
+  updated_at: SystemTime;
+}
+export type UpdateDraftCommand = {
+  UpdateDraft: {
+    name: string;
+    schedules: Record<number, TalkSchedule | null>;
+  };
+};
+export type DeleteDraftCommand = { DeleteDraft: { name: string } };
+export type PublishDraftCommand = { PublishDraft: { name: string } };
+export type CreateUserCommand = {
+  CreateUser: {
+    name: string;
+    team: string;
+    attendance_mode: null | AttendanceMode;
+    password: string;
+  };
+};
+export type CreateInviteCommand = { CreateInvite: { team: string } };
+export type ListInvitesCommand = { ListInvites: Record<string, never> };
+export type RevokeInviteCommand = { RevokeInvite: { invite_id: string } };
 
 export type Command =
   | AddTalkCommand
@@ -118,7 +179,31 @@ export type Command =
   | RemoveNoobCommand
   | AddNerdCommand
   | SetAttendanceMode
-  | RemoveNerdCommand;
+  | RemoveNerdCommand
+  | LogoutCommand
+  | LogoutAllCommand
+  | ListSessionsCommand
+  | RevokeSessionCommand
+  | RevokeTokensCommand
+  | ChangePasswordCommand
+  | IssuePasswordResetCommand
+  | GrantRoleCommand
+  | RevokeRoleCommand
+  | CreateUserCommand
+  | CreateInviteCommand
+  | ListInvitesCommand
+  | RevokeInviteCommand
+  | CreateTeamCommand
+  | RenameTeamCommand
+  | MergeTeamsCommand
+  | DeleteTeamCommand
+  | CreateLocationCommand
+  | EditLocationCommand
+  | DeleteLocationCommand
+  | ProposeScheduleCommand
+  | UpdateDraftCommand
+  | DeleteDraftCommand
+  | PublishDraftCommand;
 
 export type AuthCommand =
   | {
@@ -127,10 +212,20 @@ export type AuthCommand =
         team: string;
         password: string;
         attendance_mode: null | AttendanceMode;
+        invite_code: null | string;
       };
     }
   | { Login: { name: string; team: string; password: string } }
-  | { Relogin: { token: string } };
+  | { Relogin: { token: string } }
+  | {
+      ResetPassword: {
+        name: string;
+        team: string;
+        reset_token: string;
+        new_password: string;
+      };
+    }
+  | { Resume: { token: string; last_seq: number } };
 
 export enum AttendanceMode {
   OnSite = "OnSite",
@@ -141,6 +236,59 @@ export interface UsersPayload {
   users: Record<string, User>; // JSON keys are always strings
 }
 
+export interface Session {
+  session_id: string;
+  created_at: SystemTime;
+  last_used_at: SystemTime;
+  expires_at: SystemTime;
+  user_agent: string | null;
+  remote_address: string | null;
+  current: boolean;
+}
+
+export interface Invite {
+  invite_id: string;
+  team: string;
+  created_by: number;
+  created_at: SystemTime;
+}
+
+export type Conflict =
+  | { Location: { location_id: number; talk_ids: [number, number] } }
+  | { Nerd: { user_id: number; talk_ids: [number, number] } };
+
+export interface ScheduleProposal {
+  assignments: Record<string, { scheduled_at: SystemTime; location: number }>;
+  unplaced: number[];
+  conflicts: Conflict[];
+}
+
+export type ConflictPolicy = "Warn" | "Reject";
+
+/** The event as configured in event.json on the server. */
+export interface EventInfo {
+  name: string;
+  start_date: string; // YYYY-MM-DD
+  end_date: string; // YYYY-MM-DD, inclusive
+  timezone: string;
+  active_hours_start: string; // HH:MM:SS
+  active_hours_end: string; // HH:MM:SS
+  slot_minutes: number;
+  location_conflicts: ConflictPolicy;
+  nerd_conflicts: ConflictPolicy;
+  starts_at: SystemTime;
+  ends_at: SystemTime;
+}
+
+export interface SnapshotPayload extends UsersPayload {
+  talks: Record<string, Talk>;
+  locations: Record<string, Location>;
+  teams: string[];
+  conflicts: Conflict[];
+  drafts: Record<string, Draft>;
+  event: EventInfo;
+}
+
 export interface AuthenticationSuccessPayload {
   user_id: number;
   roles: Role[];
@@ -154,14 +302,38 @@ export interface AuthenticationErrorPayload {
 export type ServerMessage =
   | { AuthenticationSuccess: AuthenticationSuccessPayload }
   | { AuthenticationError: AuthenticationErrorPayload }
+  | { Ack: { request_id: number } }
+  | { Error: { request_id: number | null; reason: string } }
+  | { Warning: { request_id: number | null; warning: string } }
+  | { Sessions: { request_id: number | null; sessions: Session[] } }
+  | {
+      PasswordReset: {
+        request_id: number | null;
+        reset_token: string;
+        expires_at: SystemTime;
+      };
+    }
+  | {
+      Invite: {
+        request_id: number | null;
+        invite_id: string;
+        invite_code: string;
+      };
+    }
+  | { Invites: { request_id: number | null; invites: Invite[] } }
+  | { ScheduleProposal: { request_id: number | null } & ScheduleProposal }
+  | { Snapshot: SnapshotPayload }
   | { Users: UsersPayload }
   | { AddTalk: { talk: Talk } }
   | { RemoveTalk: { talk_id: number } }
+  | { UpdateCreator: { talk_id: number; creator: number } }
   | { UpdateTitle: { talk_id: number; title: string } }
   | { UpdateDescription: { talk_id: number; description: string } }
+  | { UpdateHighlight: { talk_id: number; highlight: boolean } }
   | { UpdateScheduledAt: { talk_id: number; scheduled_at: SystemTime | null } }
   | { UpdateDuration: { talk_id: number; duration: Duration } }
   | { UpdateLocation: { talk_id: number; location: number | null } }
+  | { Reschedule: { schedules: Record<string, TalkSchedule> } }
   | { AddNoob: { talk_id: number; user_id: number } }
   | { RemoveNoob: { talk_id: number; user_id: number } }
   | { AddNerd: { talk_id: number; user_id: number } }
@@ -171,4 +343,14 @@ export type ServerMessage =
         user_id: number;
         attendance_mode: AttendanceMode;
       };
-    };
+    }
+  | { UpdateRoles: { user_id: number; roles: Role[] } }
+  | { AddLocation: { location: Location } }
+  | { ChangeLocation: { location: Location } }
+  | { RemoveLocation: { location_id: number } }
+  | { AddTeam: { team: string } }
+  | { RemoveTeam: { team: string } }
+  | { RenameTeam: { team: string; new_name: string } }
+  | { Conflicts: { conflicts: Conflict[] } }
+  | { UpdateDraft: { name: string; draft: Draft | null } }
+  | { UpdateEvent: { event: EventInfo } };
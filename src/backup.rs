@@ -0,0 +1,274 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use eyre::{bail, Context as _};
+use jiff::Timestamp;
+use rusqlite::Connection;
+use tokio::{sync::RwLock, task::spawn_blocking, time::interval};
+use tracing::{error, info};
+
+use crate::{
+    event::EVENT_FILE,
+    journal::{ARCHIVE_DIRECTORY, JOURNAL_FILE},
+    json_backend::COLLECTION_FILES,
+    migration::VERSION_FILE,
+    sqlite_backend::DATABASE_FILE,
+    storage::Storage,
+};
+
+/// Files and directories in the storage directory that make up the storage.
+///
+/// Backups and restores only touch these, so e.g. a backup directory inside the storage directory
+/// is left alone.
+fn storage_entries() -> impl Iterator<Item = String> {
+    COLLECTION_FILES
+        .into_iter()
+        .chain([
+            VERSION_FILE,
+            EVENT_FILE,
+            JOURNAL_FILE,
+            ARCHIVE_DIRECTORY,
+            DATABASE_FILE,
+        ])
+        .map(str::to_string)
+        .chain(database_journal_files())
+}
+
+/// Write-ahead log and shared memory of the SQLite database, which must not outlive the database
+/// they belong to.
+fn database_journal_files() -> [String; 2] {
+    [
+        format!("{DATABASE_FILE}-wal"),
+        format!("{DATABASE_FILE}-shm"),
+    ]
+}
+
+/// Timestamped copies of the storage directory, of which only the newest ones are kept.
+#[derive(Clone, Debug)]
+pub struct Backups {
+    directory: PathBuf,
+    retention: usize,
+}
+
+impl Backups {
+    pub fn new(directory: PathBuf, retention: usize) -> Self {
+        Self {
+            directory,
+            retention: retention.max(1),
+        }
+    }
+
+    /// Copies the storage into a new backup and removes backups beyond the retention.
+    ///
+    /// Callers must hold a lock on the storage so that no writes happen during the copy.
+    pub async fn create(&self, storage_path: &Path) -> eyre::Result<String> {
+        let name = self.copy_into_new_backup(storage_path).await?;
+        let directory = self.directory.clone();
+        let retention = self.retention;
+        spawn_blocking(move || prune(&directory, retention))
+            .await
+            .wrap_err("failed to join backup task")?
+            .wrap_err("failed to remove old backups")?;
+        Ok(name)
+    }
+
+    async fn copy_into_new_backup(&self, storage_path: &Path) -> eyre::Result<String> {
+        let timestamp = Timestamp::now().strftime("%Y%m%dT%H%M%SZ").to_string();
+        let storage_path = storage_path.to_path_buf();
+        let directory = self.directory.clone();
+        spawn_blocking(move || {
+            fs::create_dir_all(&directory).wrap_err("failed to create backup directory")?;
+            let directory = directory
+                .canonicalize()
+                .wrap_err("failed to resolve backup directory")?;
+            let storage_path = storage_path
+                .canonicalize()
+                .wrap_err("failed to resolve storage directory")?;
+            let mut name = timestamp.clone();
+            let mut suffix = 1;
+            while directory.join(&name).exists() {
+                name = format!("{timestamp}-{suffix}");
+                suffix += 1;
+            }
+            copy_storage(&storage_path, &directory.join(&name))
+                .wrap_err_with(|| format!("failed to copy storage into backup {name}"))?;
+            Ok(name)
+        })
+        .await
+        .wrap_err("failed to join backup task")?
+    }
+
+    /// Names of all backups, oldest first.
+    pub async fn list(&self) -> eyre::Result<Vec<String>> {
+        let directory = self.directory.clone();
+        spawn_blocking(move || list(&directory))
+            .await
+            .wrap_err("failed to join backup task")?
+    }
+
+    /// Replaces the storage in the storage directory with the given backup.
+    ///
+    /// The server must not be running on this storage directory. The current contents are backed
+    /// up first, so a restore can be undone by restoring that backup.
+    pub async fn restore(&self, name: &str, storage_path: &Path) -> eyre::Result<String> {
+        if !self.list().await?.iter().any(|backup| backup == name) {
+            bail!("unknown backup {name}");
+        }
+        // Not pruning here, the backup to restore may be the oldest one
+        let safety_backup = self
+            .copy_into_new_backup(storage_path)
+            .await
+            .wrap_err("failed to back up current storage")?;
+        let source = self.directory.join(name);
+        let storage_path = storage_path.to_path_buf();
+        spawn_blocking(move || -> eyre::Result<()> {
+            for entry in storage_entries() {
+                let path = storage_path.join(entry);
+                if path.is_dir() {
+                    fs::remove_dir_all(&path)
+                } else if path.exists() {
+                    fs::remove_file(&path)
+                } else {
+                    continue;
+                }
+                .wrap_err_with(|| format!("failed to remove {}", path.display()))?;
+            }
+            copy_storage(&source, &storage_path)
+        })
+        .await
+        .wrap_err("failed to join backup task")??;
+        Ok(safety_backup)
+    }
+}
+
+/// Copies the entries of a storage directory, taking a consistent snapshot of the database.
+fn copy_storage(source: &Path, destination: &Path) -> eyre::Result<()> {
+    fs::create_dir_all(destination)
+        .wrap_err_with(|| format!("failed to create {}", destination.display()))?;
+    let database_journal_files = database_journal_files();
+    for entry in storage_entries() {
+        let path = source.join(&entry);
+        let target = destination.join(&entry);
+        if !path.exists() || database_journal_files.contains(&entry) {
+            continue;
+        }
+        if entry == DATABASE_FILE {
+            // Copying the file could miss writes that are still in the write-ahead log
+            Connection::open(&path)
+                .and_then(|connection| {
+                    connection.execute("VACUUM INTO ?1", [target.to_string_lossy()])
+                })
+                .wrap_err_with(|| format!("failed to copy {}", path.display()))?;
+        } else if path.is_dir() {
+            copy_directory(&path, &target)?;
+        } else {
+            fs::copy(&path, &target)
+                .wrap_err_with(|| format!("failed to copy {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn copy_directory(source: &Path, destination: &Path) -> eyre::Result<()> {
+    fs::create_dir_all(destination)
+        .wrap_err_with(|| format!("failed to create {}", destination.display()))?;
+    for entry in
+        fs::read_dir(source).wrap_err_with(|| format!("failed to read {}", source.display()))?
+    {
+        let path = entry.wrap_err("failed to read directory entry")?.path();
+        if path.extension().is_some_and(|extension| extension == "tmp") {
+            continue;
+        }
+        let Some(file_name) = path.file_name() else {
+            continue;
+        };
+        let target = destination.join(file_name);
+        if path.is_dir() {
+            copy_directory(&path, &target)?;
+        } else {
+            fs::copy(&path, &target)
+                .wrap_err_with(|| format!("failed to copy {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn list(directory: &Path) -> eyre::Result<Vec<String>> {
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in fs::read_dir(directory).wrap_err("failed to read backup directory")? {
+        let entry = entry.wrap_err("failed to read backup entry")?;
+        if entry.path().is_dir() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort_by(|left, right| backup_order(left).cmp(&backup_order(right)));
+    Ok(names)
+}
+
+/// Orders backups by their timestamp, then by the suffix of backups created in the same second.
+fn backup_order(name: &str) -> (&str, u32) {
+    name.rsplit_once('-')
+        .and_then(|(timestamp, suffix)| Some((timestamp, suffix.parse().ok()?)))
+        .unwrap_or((name, 0))
+}
+
+fn prune(directory: &Path, retention: usize) -> eyre::Result<()> {
+    let names = list(directory)?;
+    let excess = names.len().saturating_sub(retention);
+    for name in &names[..excess] {
+        info!("Removing old backup {name}");
+        fs::remove_dir_all(directory.join(name))
+            .wrap_err_with(|| format!("failed to remove backup {name}"))?;
+    }
+    Ok(())
+}
+
+pub async fn back_up_periodically(
+    storage: Arc<RwLock<Storage>>,
+    backups: Backups,
+    period: Duration,
+) {
+    let mut ticks = interval(period);
+    loop {
+        ticks.tick().await;
+        let storage = storage.read().await;
+        match backups.create(&storage.path).await {
+            Ok(name) => info!("Created backup {name}"),
+            Err(error) => error!("Failed to create backup: {error:#?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backups_of_the_same_second_are_ordered_by_suffix() {
+        let mut names = [
+            "20251128T100000Z-10",
+            "20251128T100001Z",
+            "20251128T100000Z-2",
+            "20251128T100000Z",
+            "20251128T095959Z-1",
+        ];
+        names.sort_by(|left, right| backup_order(left).cmp(&backup_order(right)));
+        assert_eq!(
+            names,
+            [
+                "20251128T095959Z-1",
+                "20251128T100000Z",
+                "20251128T100000Z-2",
+                "20251128T100000Z-10",
+                "20251128T100001Z",
+            ]
+        );
+    }
+}
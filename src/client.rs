@@ -1,109 +1,206 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    future::pending,
+    net::SocketAddr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime},
+};
+
 use axum::{
     extract::{
-        ws::{Message, WebSocket},
-        State, WebSocketUpgrade,
+        ws::{close_code, CloseFrame, Message, WebSocket},
+        ConnectInfo, State, WebSocketUpgrade,
     },
+    http::{header::USER_AGENT, HeaderMap},
     response::IntoResponse,
 };
-use eyre::{bail, Context, ContextCompat as _, Result};
-use tokio::select;
-use tracing::error;
+use eyre::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::{
+    select,
+    sync::broadcast::{self, error::RecvError},
+    time::{interval_at, sleep_until, timeout, Instant},
+};
+use tracing::{error, info, warn};
 
 use crate::{
-    messages::{AuthenticationCommand, AuthenticationResponse, Command, Update},
-    service::{Authentication, Service},
-    storage::{AttendanceMode, UserId},
+    conflicts::{find_conflicts, visible_conflicts},
+    messages::{
+        AuthenticationCommand, AuthenticationResponse, Command, CommandResponse, Invite, Session,
+        Update,
+    },
+    service::{Authentication, Peer, Revocation, Service},
+    solver::ScheduleProposal,
+    storage::{AttendanceMode, InviteId, Location, Role, UserId},
+    updates::SequencedUpdate,
 };
 
+static OPEN_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+static DEAD_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// How often the server pings each client and how long it waits for any answer.
+#[derive(Clone, Copy, Debug)]
+pub struct Heartbeat {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
 pub async fn handle_websocket(
     State(service): State<Service>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     upgrade: WebSocketUpgrade,
 ) -> impl IntoResponse {
-    upgrade.on_upgrade(move |socket| handle_upgraded_websocket(socket, service))
+    let peer = Peer {
+        user_agent: headers
+            .get(USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .map(ToString::to_string),
+        address,
+    };
+    upgrade.on_upgrade(move |socket| handle_upgraded_websocket(socket, service, peer))
 }
 
-async fn handle_upgraded_websocket(socket: WebSocket, service: Service) {
-    match connection(socket, service).await {
+async fn handle_upgraded_websocket(socket: WebSocket, service: Service, peer: Peer) {
+    let open_connections = OPEN_CONNECTIONS.fetch_add(1, Ordering::Relaxed) + 1;
+    info!("Connection opened, {open_connections} open connections");
+    match connection(socket, service, peer).await {
         Ok(_) => {}
         Err(error) => error!("Error in handle_upgraded_websocket(): {error:#?}"),
     }
+    let open_connections = OPEN_CONNECTIONS.fetch_sub(1, Ordering::Relaxed) - 1;
+    info!("Connection closed, {open_connections} open connections");
 }
 
-async fn connection(mut socket: WebSocket, service: Service) -> Result<()> {
-    let mut updates_receiver = service.updates_sender.subscribe();
+async fn connection(mut socket: WebSocket, service: Service, peer: Peer) -> Result<()> {
+    // Subscribe before authenticating to not miss revocations of the session being authenticated
+    let mut revocations_receiver = service.revocations_sender.subscribe();
 
-    let user_id = match authenticate(&mut socket, &service).await {
-        Ok(authentication) => {
-            let response = AuthenticationResponse::AuthenticationSuccess {
-                user_id: authentication.user_id,
-                roles: authentication.roles.clone(),
-                token: authentication.token,
-            };
-            let _ = socket
-                .send(Message::Text(
-                    serde_json::to_string(&response).unwrap().into(),
-                ))
-                .await;
-            authentication.user_id
-        }
-        Err(error) => {
-            let response = AuthenticationResponse::AuthenticationError {
-                reason: format!("{error:#}"),
-            };
-            let _ = socket
-                .send(Message::Text(
-                    serde_json::to_string(&response).unwrap().into(),
-                ))
-                .await;
-            return Ok(());
-        }
+    let heartbeat = service.heartbeat;
+    let Some(authentication_message) = receive_first_message(&mut socket, heartbeat).await? else {
+        return Ok(());
     };
+    let (user_id, session_id, mut roles, resume_after) =
+        match authenticate(authentication_message, &service, &peer).await {
+            Ok((authentication, resume_after)) => {
+                let response = AuthenticationResponse::AuthenticationSuccess {
+                    user_id: authentication.user_id,
+                    roles: authentication.roles.clone(),
+                    token: authentication.token.clone(),
+                };
+                let _ = socket
+                    .send(Message::Text(
+                        serde_json::to_string(&response).unwrap().into(),
+                    ))
+                    .await;
+                (
+                    authentication.user_id,
+                    authentication.session_id,
+                    authentication.roles,
+                    resume_after,
+                )
+            }
+            Err(error) => {
+                let response = AuthenticationResponse::AuthenticationError {
+                    reason: format!("{error:#}"),
+                };
+                let _ = socket
+                    .send(Message::Text(
+                        serde_json::to_string(&response).unwrap().into(),
+                    ))
+                    .await;
+                return Ok(());
+            }
+        };
 
-    let update_users = Update::Users {
-        users: service
-            .storage
-            .read()
-            .await
-            .users
-            .values()
-            .map(|user| (user.id, user.into()))
-            .collect(),
-    };
-    let _ = socket
-        .send(Message::Text(
-            serde_json::to_string(&update_users).unwrap().into(),
-        ))
-        .await;
+    let (mut updates_receiver, mut last_seq) =
+        synchronize(&mut socket, &service, user_id, &mut roles, resume_after).await?;
 
-    {
-        let talks = &service.storage.read().await.talks;
-        for talk in talks.values() {
-            let update = Update::AddTalk { talk: talk.clone() };
-            let _ = socket
-                .send(Message::Text(
-                    serde_json::to_string(&update)
-                        .wrap_err_with(|| format!("failed to serialize talk update: {update:#?}"))?
-                        .into(),
-                ))
-                .await;
-        }
-    }
+    let mut pings = interval_at(Instant::now() + heartbeat.interval, heartbeat.interval);
+    let mut pong_deadline = None;
 
     loop {
         select! {
+            _ = pings.tick(), if pong_deadline.is_none() => {
+                socket
+                    .send(Message::Ping(Default::default()))
+                    .await
+                    .wrap_err("failed to send ping")?;
+                pong_deadline = Some(Instant::now() + heartbeat.timeout);
+            }
+            _ = async {
+                match pong_deadline {
+                    Some(deadline) => sleep_until(deadline).await,
+                    None => pending().await,
+                }
+            } => {
+                let dead_connections = DEAD_CONNECTIONS.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(
+                    "Connection of user {user_id} did not answer ping within {:?}, closing it \
+                    ({dead_connections} dead connections so far)",
+                    heartbeat.timeout
+                );
+                close(&mut socket, heartbeat, close_code::AWAY, "ping timeout").await;
+                break;
+            }
+            revocation = revocations_receiver.recv() => {
+                let reason = match revocation {
+                    Ok(Revocation::Session(revoked_session_id)) if revoked_session_id == session_id => {
+                        "logged out"
+                    }
+                    Ok(Revocation::User(revoked_user_id)) if revoked_user_id == user_id => {
+                        "all sessions revoked"
+                    }
+                    Ok(_) => continue,
+                    // The token may have been revoked, the client can log in again if it was not
+                    Err(RecvError::Lagged(_)) => "missed revocations",
+                    Err(error) => return Err(error).wrap_err("failed to receive revocation"),
+                };
+                info!("Closing connection of user {user_id}: {reason}");
+                close(&mut socket, heartbeat, close_code::POLICY, reason).await;
+                break;
+            }
             command_message = socket.recv() => {
-                if command_message.is_none() {
+                let Some(command_message) = command_message else {
                     break;
+                };
+                let command_message = command_message.wrap_err("failed to receive command")?;
+                // Any message shows that the client is alive, not only pongs
+                pong_deadline = None;
+                if let Some(response) =
+                    handle_message(command_message, user_id, &session_id, &service).await
+                {
+                    socket
+                        .send(Message::Text(
+                            serde_json::to_string(&response)
+                                .wrap_err("failed to serialize command response")?
+                                .into(),
+                        ))
+                        .await
+                        .wrap_err("failed to send command response")?;
                 }
-                handle_message(command_message.unwrap(), user_id, &service)
-                    .await
-                    .wrap_err("failed to handle command message")?;
             }
             update = updates_receiver.recv() => {
-                let update = update.wrap_err("failed to receive update")?;
-                handle_update(update, &mut socket)
-                    .await
-                    .wrap_err("failed to handle update")?;
+                match update {
+                    Ok(update) => {
+                        last_seq = update.seq;
+                        let Some(update) = visible_update(&update, user_id, &mut roles) else {
+                            continue;
+                        };
+                        handle_update(&update, &mut socket)
+                            .await
+                            .wrap_err("failed to handle update")?;
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Connection of user {user_id} lagged behind by {skipped} updates");
+                        (updates_receiver, last_seq) =
+                            synchronize(&mut socket, &service, user_id, &mut roles, Some(last_seq))
+                                .await?;
+                    }
+                    Err(error) => return Err(error).wrap_err("failed to receive update"),
+                }
             }
         }
     }
@@ -111,12 +208,156 @@ async fn connection(mut socket: WebSocket, service: Service) -> Result<()> {
     Ok(())
 }
 
-pub async fn authenticate(
+/// Sends a close frame without waiting for long, since the client may be gone already.
+async fn close(socket: &mut WebSocket, heartbeat: Heartbeat, code: u16, reason: &'static str) {
+    let close = Message::Close(Some(CloseFrame {
+        code,
+        reason: reason.into(),
+    }));
+    let _ = timeout(heartbeat.timeout, socket.send(close)).await;
+}
+
+/// The update as the user gets to see it, if at all, keeping the user's roles up to date along
+/// the way.
+fn visible_update<'a>(
+    update: &'a SequencedUpdate,
+    user_id: UserId,
+    roles: &mut BTreeSet<Role>,
+) -> Option<SequencedUpdate<Cow<'a, Update>>> {
+    if let Update::UpdateRoles {
+        user_id: target_user_id,
+        roles: new_roles,
+    } = &update.update
+    {
+        if *target_user_id == user_id {
+            *roles = new_roles.clone();
+        }
+    }
+    Some(SequencedUpdate {
+        seq: update.seq,
+        update: update.update.visible_to(user_id, roles)?,
+    })
+}
+
+/// Brings the client up to date and subscribes to further updates.
+///
+/// With `resume_after`, only updates after that sequence number are sent if they are all still
+/// buffered. Otherwise the client receives the full state. Returns the receiver for further updates
+/// and the sequence number the client is at.
+async fn synchronize(
     socket: &mut WebSocket,
     service: &Service,
-) -> eyre::Result<Authentication> {
-    let maybe_message = socket.recv().await.wrap_err("WebSocket closed")?;
-    let message = maybe_message.wrap_err("failed to receive message from WebSocket")?;
+    user_id: UserId,
+    roles: &mut BTreeSet<Role>,
+    resume_after: Option<u64>,
+) -> Result<(broadcast::Receiver<SequencedUpdate>, u64)> {
+    if let Some(last_seq) = resume_after {
+        let (missed_updates, updates_receiver) = service.updates_sender.subscribe_after(last_seq);
+        if let Some(missed_updates) = missed_updates {
+            let mut last_seq = last_seq;
+            for update in missed_updates {
+                last_seq = update.seq;
+                let Some(update) = visible_update(&update, user_id, roles) else {
+                    continue;
+                };
+                handle_update(&update, socket)
+                    .await
+                    .wrap_err("failed to send missed update")?;
+            }
+            return Ok((updates_receiver, last_seq));
+        }
+        info!("Updates after {last_seq} are no longer buffered, sending full state");
+    }
+
+    // Holding the read lock prevents updates between taking the snapshot and subscribing
+    let storage = service.storage.read().await;
+    let (seq, updates_receiver) = service.updates_sender.subscribe();
+    if let Some(user) = storage.users.get(&user_id) {
+        *roles = user.roles.clone();
+    }
+
+    let snapshot = SequencedUpdate {
+        seq,
+        update: Update::Snapshot {
+            users: storage
+                .users
+                .values()
+                .map(|user| (user.id, user.into()))
+                .collect(),
+            talks: storage.talks.clone(),
+            locations: storage.locations.clone(),
+            teams: storage.teams.clone(),
+            conflicts: visible_conflicts(&find_conflicts(&storage.talks), user_id, roles),
+            drafts: if roles.contains(&Role::Scheduler) {
+                storage.drafts.clone()
+            } else {
+                BTreeMap::new()
+            },
+            event: storage.event.clone(),
+        },
+    };
+    drop(storage);
+    handle_update(&snapshot, socket)
+        .await
+        .wrap_err("failed to send snapshot")?;
+
+    Ok((updates_receiver, seq))
+}
+
+/// Authenticates the client, returning the sequence number to resume after if it asked to resume.
+/// Waits for the first message of the client, pinging it meanwhile to notice dead connections.
+///
+/// Returns `None` if the client closed the connection or stopped answering pings.
+async fn receive_first_message(
+    socket: &mut WebSocket,
+    heartbeat: Heartbeat,
+) -> Result<Option<Message>> {
+    let mut pings = interval_at(Instant::now() + heartbeat.interval, heartbeat.interval);
+    let mut pong_deadline = None;
+
+    loop {
+        select! {
+            _ = pings.tick(), if pong_deadline.is_none() => {
+                socket
+                    .send(Message::Ping(Default::default()))
+                    .await
+                    .wrap_err("failed to send ping")?;
+                pong_deadline = Some(Instant::now() + heartbeat.timeout);
+            }
+            _ = async {
+                match pong_deadline {
+                    Some(deadline) => sleep_until(deadline).await,
+                    None => pending().await,
+                }
+            } => {
+                let dead_connections = DEAD_CONNECTIONS.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(
+                    "Unauthenticated connection did not answer ping within {:?}, closing it \
+                    ({dead_connections} dead connections so far)",
+                    heartbeat.timeout
+                );
+                close(socket, heartbeat, close_code::AWAY, "ping timeout").await;
+                return Ok(None);
+            }
+            message = socket.recv() => {
+                let Some(message) = message else {
+                    return Ok(None);
+                };
+                match message.wrap_err("failed to receive message from WebSocket")? {
+                    Message::Ping(_) | Message::Pong(_) => pong_deadline = None,
+                    Message::Close(_) => return Ok(None),
+                    message => return Ok(Some(message)),
+                }
+            }
+        }
+    }
+}
+
+pub async fn authenticate(
+    message: Message,
+    service: &Service,
+    peer: &Peer,
+) -> eyre::Result<(Authentication, Option<u64>)> {
     let text = match message {
         Message::Text(text) => text,
         other => bail!("expected text message from WebSocket, got: {other:#?}"),
@@ -129,31 +370,56 @@ pub async fn authenticate(
             team,
             attendance_mode,
             password,
+            invite_code,
         } => service
             .register(
                 name,
                 team,
                 attendance_mode.unwrap_or(AttendanceMode::OnSite),
                 password,
+                invite_code,
+                peer,
             )
             .await
+            .map(|authentication| (authentication, None))
             .wrap_err("failed to register"),
         AuthenticationCommand::Login {
             name,
             team,
             password,
         } => service
-            .login(name, team, password)
+            .login(name, team, password, peer)
             .await
+            .map(|authentication| (authentication, None))
             .wrap_err("failed to login"),
-        AuthenticationCommand::Relogin { token } => service.relogin(token).await,
+        AuthenticationCommand::Relogin { token } => service
+            .relogin(token, peer)
+            .await
+            .map(|authentication| (authentication, None)),
+        AuthenticationCommand::ResetPassword {
+            name,
+            team,
+            reset_token,
+            new_password,
+        } => service
+            .reset_password(name, team, reset_token, new_password, peer)
+            .await
+            .map(|authentication| (authentication, None))
+            .wrap_err("failed to reset password"),
+        AuthenticationCommand::Resume { token, last_seq } => service
+            .relogin(token, peer)
+            .await
+            .map(|authentication| (authentication, Some(last_seq))),
     }
 }
 
-async fn handle_update(update: Update, stream: &mut WebSocket) -> Result<()> {
+async fn handle_update<U>(update: &SequencedUpdate<U>, stream: &mut WebSocket) -> Result<()>
+where
+    U: Serialize,
+{
     stream
         .send(Message::Text(
-            serde_json::to_string(&update)
+            serde_json::to_string(update)
                 .wrap_err("failed to serialize update")?
                 .into(),
         ))
@@ -161,74 +427,311 @@ async fn handle_update(update: Update, stream: &mut WebSocket) -> Result<()> {
         .wrap_err("failed to send update")
 }
 
+/// Executes a command message and returns the response for the client, if any.
+///
+/// Failing commands are reported to the client instead of closing the connection. Successful
+/// commands are only acknowledged if the client sent a request id.
 async fn handle_message(
-    command_message: Result<Message, axum::Error>,
+    command_message: Message,
     user_id: UserId,
+    session_id: &str,
     service: &Service,
-) -> Result<()> {
-    let command_message = command_message.wrap_err("failed to receive command")?;
-
-    if let Message::Text(message) = command_message {
-        let command: Command =
-            serde_json::from_str(&message).wrap_err("failed to deserialize command message")?;
-
-        match command {
-            Command::AddTalk {
-                title,
-                description,
-                duration,
-            } => {
-                service
-                    .add_talk(user_id, title, description, duration)
-                    .await?;
-            }
-            Command::RemoveTalk { talk_id } => {
-                service.remove_talk(talk_id, user_id).await?;
-            }
-            Command::UpdateTitle { talk_id, title } => {
-                service.update_title(talk_id, user_id, title).await?;
-            }
-            Command::UpdateDescription {
-                talk_id,
-                description,
-            } => {
-                service
-                    .update_description(talk_id, user_id, description)
-                    .await?;
-            }
-            Command::UpdateScheduledAt {
-                talk_id,
-                scheduled_at,
-            } => {
-                service
-                    .update_scheduled_at(talk_id, user_id, scheduled_at)
-                    .await?;
-            }
-            Command::UpdateDuration { talk_id, duration } => {
-                service.update_duration(talk_id, user_id, duration).await?;
-            }
-            Command::UpdateLocation { talk_id, location } => {
-                service.update_location(talk_id, user_id, location).await?;
-            }
-            Command::AddNoob { talk_id } => {
-                service.add_noob(talk_id, user_id).await?;
-            }
-            Command::RemoveNoob { talk_id } => {
-                service.remove_noob(talk_id, user_id).await?;
-            }
-            Command::AddNerd { talk_id } => {
-                service.add_nerd(talk_id, user_id).await?;
-            }
-            Command::RemoveNerd { talk_id } => {
-                service.remove_nerd(talk_id, user_id).await?;
-            }
-            Command::SetAttendanceMode { attendance_mode } => {
-                service
-                    .set_attendance_mode(user_id, attendance_mode)
-                    .await?;
-            }
+) -> Option<CommandResponse> {
+    let Message::Text(message) = command_message else {
+        return None;
+    };
+    // Parse in two steps to be able to answer with the request id of malformed commands
+    let (request_id, result) = match serde_json::from_str::<Value>(&message) {
+        Ok(mut value) => {
+            let request_id = value
+                .as_object_mut()
+                .and_then(|object| object.remove("request_id"))
+                .and_then(|request_id| request_id.as_u64());
+            let result = match serde_json::from_value::<Command>(value)
+                .wrap_err("failed to deserialize command message")
+            {
+                Ok(command) => execute_command(command, user_id, session_id, service).await,
+                Err(error) => Err(error),
+            };
+            (request_id, result)
+        }
+        Err(error) => (
+            None,
+            Err(error).wrap_err("failed to parse command message as JSON"),
+        ),
+    };
+
+    match result {
+        Ok(Reply::Ack) => request_id.map(|request_id| CommandResponse::Ack { request_id }),
+        Ok(Reply::Warning(warning)) => Some(CommandResponse::Warning {
+            request_id,
+            warning,
+        }),
+        Ok(Reply::Sessions(sessions)) => Some(CommandResponse::Sessions {
+            request_id,
+            sessions,
+        }),
+        Ok(Reply::PasswordReset {
+            reset_token,
+            expires_at,
+        }) => Some(CommandResponse::PasswordReset {
+            request_id,
+            reset_token,
+            expires_at,
+        }),
+        Ok(Reply::Invite {
+            invite_id,
+            invite_code,
+        }) => Some(CommandResponse::Invite {
+            request_id,
+            invite_id,
+            invite_code,
+        }),
+        Ok(Reply::Invites(invites)) => Some(CommandResponse::Invites {
+            request_id,
+            invites,
+        }),
+        Ok(Reply::ScheduleProposal(proposal)) => Some(CommandResponse::ScheduleProposal {
+            request_id,
+            proposal,
+        }),
+        Err(error) => {
+            warn!("Command of user {user_id} failed: {error:#}");
+            Some(CommandResponse::Error {
+                request_id,
+                reason: format!("{error:#}"),
+            })
         }
     }
+}
 
-    Ok(())
+/// Result of a successful command, for the client that sent it.
+enum Reply {
+    Ack,
+    Warning(String),
+    Sessions(Vec<Session>),
+    PasswordReset {
+        reset_token: String,
+        expires_at: SystemTime,
+    },
+    Invite {
+        invite_id: InviteId,
+        invite_code: String,
+    },
+    Invites(Vec<Invite>),
+    ScheduleProposal(ScheduleProposal),
+}
+
+impl From<Option<String>> for Reply {
+    fn from(warning: Option<String>) -> Self {
+        warning.map_or(Reply::Ack, Reply::Warning)
+    }
+}
+
+async fn execute_command(
+    command: Command,
+    user_id: UserId,
+    session_id: &str,
+    service: &Service,
+) -> Result<Reply> {
+    match command {
+        Command::AddTalk {
+            title,
+            description,
+            duration,
+        } => {
+            service
+                .add_talk(user_id, title, description, duration)
+                .await?;
+        }
+        Command::RemoveTalk { talk_id } => {
+            service.remove_talk(talk_id, user_id).await?;
+        }
+        Command::UpdateTitle { talk_id, title } => {
+            service.update_title(talk_id, user_id, title).await?;
+        }
+        Command::UpdateDescription {
+            talk_id,
+            description,
+        } => {
+            service
+                .update_description(talk_id, user_id, description)
+                .await?;
+        }
+        Command::UpdateScheduledAt {
+            talk_id,
+            scheduled_at,
+        } => {
+            return service
+                .update_scheduled_at(talk_id, user_id, scheduled_at)
+                .await
+                .map(Reply::from);
+        }
+        Command::UpdateDuration { talk_id, duration } => {
+            return service
+                .update_duration(talk_id, user_id, duration)
+                .await
+                .map(Reply::from);
+        }
+        Command::UpdateLocation { talk_id, location } => {
+            return service
+                .update_location(talk_id, user_id, location)
+                .await
+                .map(Reply::from);
+        }
+        Command::AddNoob { talk_id } => {
+            service.add_noob(talk_id, user_id).await?;
+        }
+        Command::RemoveNoob { talk_id } => {
+            service.remove_noob(talk_id, user_id).await?;
+        }
+        Command::AddNerd { talk_id } => {
+            return service.add_nerd(talk_id, user_id).await.map(Reply::from);
+        }
+        Command::RemoveNerd { talk_id } => {
+            service.remove_nerd(talk_id, user_id).await?;
+        }
+        Command::SetAttendanceMode { attendance_mode } => {
+            service
+                .set_attendance_mode(user_id, attendance_mode)
+                .await?;
+        }
+        Command::Logout {} => {
+            service
+                .revoke_session(user_id, session_id.to_string())
+                .await?;
+        }
+        Command::LogoutAll {} => {
+            service.logout_all(user_id).await?;
+        }
+        Command::ListSessions {} => {
+            return Ok(Reply::Sessions(
+                service.list_sessions(user_id, session_id).await,
+            ));
+        }
+        Command::RevokeSession { session_id } => {
+            service.revoke_session(user_id, session_id).await?;
+        }
+        Command::ChangePassword { old, new } => {
+            service
+                .change_password(user_id, session_id, old, new)
+                .await?;
+        }
+        Command::IssuePasswordReset {
+            user_id: target_user_id,
+        } => {
+            let (reset_token, expires_at) = service
+                .issue_password_reset(user_id, target_user_id)
+                .await?;
+            return Ok(Reply::PasswordReset {
+                reset_token,
+                expires_at,
+            });
+        }
+        Command::RevokeTokens {
+            user_id: target_user_id,
+        } => {
+            service.revoke_tokens(user_id, target_user_id).await?;
+        }
+        Command::GrantRole {
+            user_id: target_user_id,
+            role,
+        } => {
+            service.grant_role(user_id, target_user_id, role).await?;
+        }
+        Command::RevokeRole {
+            user_id: target_user_id,
+            role,
+        } => {
+            service.revoke_role(user_id, target_user_id, role).await?;
+        }
+        Command::CreateUser {
+            name,
+            team,
+            attendance_mode,
+            password,
+        } => {
+            service
+                .create_user(
+                    user_id,
+                    name,
+                    team,
+                    attendance_mode.unwrap_or(AttendanceMode::OnSite),
+                    password,
+                )
+                .await?;
+        }
+        Command::CreateInvite { team } => {
+            let (invite_id, invite_code) = service.create_invite(user_id, team).await?;
+            return Ok(Reply::Invite {
+                invite_id,
+                invite_code,
+            });
+        }
+        Command::ListInvites {} => {
+            return Ok(Reply::Invites(service.list_invites(user_id).await?));
+        }
+        Command::RevokeInvite { invite_id } => {
+            service.revoke_invite(user_id, invite_id).await?;
+        }
+        Command::CreateTeam { team } => {
+            service.create_team(user_id, team).await?;
+        }
+        Command::RenameTeam { team, new_name } => {
+            service.rename_team(user_id, team, new_name).await?;
+        }
+        Command::MergeTeams { team, into } => {
+            service.merge_teams(user_id, team, into).await?;
+        }
+        Command::DeleteTeam { team } => {
+            service.delete_team(user_id, team).await?;
+        }
+        Command::CreateLocation {
+            name,
+            live_stream,
+            capacity,
+            building,
+            floor,
+            accessibility_notes,
+            map_url,
+        } => {
+            service
+                .create_location(
+                    user_id,
+                    Location {
+                        id: 0,
+                        name,
+                        live_stream,
+                        capacity,
+                        building,
+                        floor,
+                        accessibility_notes,
+                        map_url,
+                    },
+                )
+                .await?;
+        }
+        Command::EditLocation { location } => {
+            service.edit_location(user_id, location).await?;
+        }
+        Command::DeleteLocation { location_id } => {
+            service.delete_location(user_id, location_id).await?;
+        }
+        Command::ProposeSchedule {} => {
+            return Ok(Reply::ScheduleProposal(
+                service.propose_schedule(user_id).await?,
+            ));
+        }
+        Command::UpdateDraft { name, schedules } => {
+            service.update_draft(user_id, name, schedules).await?;
+        }
+        Command::DeleteDraft { name } => {
+            service.delete_draft(user_id, name).await?;
+        }
+        Command::PublishDraft { name } => {
+            return service.publish_draft(user_id, name).await.map(Reply::from);
+        }
+    }
+
+    Ok(Reply::Ack)
 }
@@ -0,0 +1,232 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{Location, Role, Talk, User, UserId};
+
+/// Two talks that cannot take place as scheduled, with their ids in ascending order.
+#[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Conflict {
+    /// Both talks take place at the location at overlapping times.
+    Location {
+        location_id: usize,
+        talk_ids: [usize; 2],
+    },
+    /// The user is a nerd of both talks, which take place at overlapping times.
+    Nerd {
+        user_id: UserId,
+        talk_ids: [usize; 2],
+    },
+}
+
+impl Conflict {
+    /// Describes the conflict with the names of the location or user.
+    pub fn describe(
+        &self,
+        users: &BTreeMap<UserId, User>,
+        locations: &BTreeMap<usize, Location>,
+    ) -> String {
+        match self {
+            Conflict::Location {
+                location_id,
+                talk_ids: [talk_id, other_talk_id],
+            } => {
+                let location = locations
+                    .get(location_id)
+                    .map_or_else(|| location_id.to_string(), |location| location.name.clone());
+                format!("talks {talk_id} and {other_talk_id} overlap at {location}")
+            }
+            Conflict::Nerd {
+                user_id,
+                talk_ids: [talk_id, other_talk_id],
+            } => {
+                let name = users
+                    .get(user_id)
+                    .map_or_else(|| user_id.to_string(), |user| user.name.clone());
+                format!("{name} is a nerd of the overlapping talks {talk_id} and {other_talk_id}")
+            }
+        }
+    }
+}
+
+/// What happens to changes that introduce conflicts.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ConflictPolicy {
+    /// Apply the change and warn the user about the conflicts.
+    #[default]
+    Warn,
+    /// Refuse the change.
+    Reject,
+}
+
+/// The conflicts the user is shown: all of them to schedulers, otherwise only the user's own.
+pub fn visible_conflicts(
+    conflicts: &BTreeSet<Conflict>,
+    user_id: UserId,
+    roles: &BTreeSet<Role>,
+) -> BTreeSet<Conflict> {
+    if roles.contains(&Role::Scheduler) {
+        return conflicts.clone();
+    }
+    conflicts
+        .iter()
+        .filter(|conflict| {
+            matches!(conflict, Conflict::Nerd { user_id: nerd_id, .. } if *nerd_id == user_id)
+        })
+        .cloned()
+        .collect()
+}
+
+/// All conflicts between the talks.
+pub fn find_conflicts(talks: &BTreeMap<usize, Talk>) -> BTreeSet<Conflict> {
+    talks
+        .values()
+        .flat_map(|talk| conflicts_of(talk, talks))
+        .collect()
+}
+
+/// Conflicts of the talk with the other talks, which may contain an older version of it.
+pub fn conflicts_of(talk: &Talk, talks: &BTreeMap<usize, Talk>) -> BTreeSet<Conflict> {
+    let Some(span) = time_span(talk) else {
+        return BTreeSet::new();
+    };
+    talks
+        .values()
+        .filter(|other| other.id != talk.id)
+        .filter(|other| time_span(other).is_some_and(|other_span| overlap(span, other_span)))
+        .flat_map(|other| {
+            let talk_ids = ordered(talk.id, other.id);
+            let location_conflict = talk
+                .location
+                .filter(|location_id| other.location == Some(*location_id))
+                .map(|location_id| Conflict::Location {
+                    location_id,
+                    talk_ids,
+                });
+            let nerd_conflicts =
+                talk.nerds
+                    .intersection(&other.nerds)
+                    .map(move |user_id| Conflict::Nerd {
+                        user_id: *user_id,
+                        talk_ids,
+                    });
+            location_conflict.into_iter().chain(nerd_conflicts)
+        })
+        .collect()
+}
+
+fn time_span(talk: &Talk) -> Option<(SystemTime, SystemTime)> {
+    let start = talk.scheduled_at?;
+    Some((start, start.checked_add(talk.duration)?))
+}
+
+fn overlap(
+    (start, end): (SystemTime, SystemTime),
+    (other_start, other_end): (SystemTime, SystemTime),
+) -> bool {
+    start < other_end && other_start < end
+}
+
+fn ordered(talk_id: usize, other_talk_id: usize) -> [usize; 2] {
+    [talk_id.min(other_talk_id), talk_id.max(other_talk_id)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{talk, talks};
+
+    #[test]
+    fn overlapping_talks_at_a_location_conflict() {
+        let talks = talks([
+            talk(1, Some(10), Some(0), &[]),
+            talk(2, Some(10), Some(0), &[]),
+        ]);
+        assert_eq!(
+            find_conflicts(&talks),
+            BTreeSet::from([Conflict::Location {
+                location_id: 0,
+                talk_ids: [1, 2],
+            }])
+        );
+    }
+
+    #[test]
+    fn touching_talks_do_not_conflict() {
+        let talks = talks([
+            talk(1, Some(10), Some(0), &[7]),
+            talk(2, Some(11), Some(0), &[7]),
+        ]);
+        assert!(find_conflicts(&talks).is_empty());
+    }
+
+    #[test]
+    fn talks_at_other_or_without_locations_do_not_conflict_at_a_location() {
+        let talks = talks([
+            talk(1, Some(10), Some(0), &[]),
+            talk(2, Some(10), Some(1), &[]),
+            talk(3, Some(10), None, &[]),
+            talk(4, Some(10), None, &[]),
+        ]);
+        assert!(find_conflicts(&talks).is_empty());
+    }
+
+    #[test]
+    fn unscheduled_talks_do_not_conflict() {
+        let talks = talks([
+            talk(1, Some(10), Some(0), &[7]),
+            talk(2, None, Some(0), &[7]),
+        ]);
+        assert!(find_conflicts(&talks).is_empty());
+    }
+
+    #[test]
+    fn nerds_of_overlapping_talks_conflict() {
+        let talks = talks([
+            talk(1, Some(10), None, &[7, 8]),
+            talk(2, Some(10), Some(0), &[7]),
+        ]);
+        assert_eq!(
+            find_conflicts(&talks),
+            BTreeSet::from([Conflict::Nerd {
+                user_id: 7,
+                talk_ids: [1, 2],
+            }])
+        );
+    }
+
+    #[test]
+    fn talk_ids_are_ordered() {
+        let talks = talks([
+            talk(1, Some(10), Some(0), &[7]),
+            talk(5, Some(10), Some(0), &[7]),
+        ]);
+        let expected = BTreeSet::from([
+            Conflict::Location {
+                location_id: 0,
+                talk_ids: [1, 5],
+            },
+            Conflict::Nerd {
+                user_id: 7,
+                talk_ids: [1, 5],
+            },
+        ]);
+        assert_eq!(conflicts_of(&talks[&5], &talks), expected);
+        assert_eq!(conflicts_of(&talks[&1], &talks), expected);
+    }
+
+    #[test]
+    fn conflicts_of_ignore_the_older_version_of_the_talk() {
+        let mut talks = talks([
+            talk(1, Some(10), Some(0), &[]),
+            talk(2, Some(12), Some(0), &[]),
+        ]);
+        let moved_talk = talk(1, Some(12), Some(0), &[]);
+        assert_eq!(conflicts_of(&moved_talk, &talks).len(), 1);
+        talks.insert(1, moved_talk);
+        assert_eq!(find_conflicts(&talks).len(), 1);
+    }
+}
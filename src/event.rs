@@ -0,0 +1,280 @@
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use eyre::{bail, Context as _};
+use jiff::{
+    civil::{date, time, Date, Time},
+    tz::TimeZone,
+};
+use serde::{Deserialize, Serialize};
+use tokio::fs::try_exists;
+use tracing::warn;
+
+use crate::{
+    conflicts::{Conflict, ConflictPolicy},
+    json_file::read_from_file_or_create_default,
+    solver::TimeWindow,
+    storage::Talk,
+};
+
+pub const EVENT_FILE: &str = "event.json";
+
+/// Configuration of the event in `event.json`, edited by hand.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct EventConfig {
+    pub name: String,
+    /// First day of the event.
+    pub start_date: Date,
+    /// Last day of the event, inclusive.
+    pub end_date: Date,
+    /// IANA name of the timezone the dates and active hours are in, e.g. `Europe/Berlin`.
+    pub timezone: String,
+    /// Daily time from which talks usually take place.
+    pub active_hours_start: Time,
+    /// Daily time until which talks usually take place.
+    pub active_hours_end: Time,
+    /// Granularity of start times in the scheduler.
+    pub slot_minutes: u32,
+    /// What happens to scheduling changes that make talks overlap at a location.
+    #[serde(default)]
+    pub location_conflicts: ConflictPolicy,
+    /// What happens to scheduling changes that make a nerd attend overlapping talks.
+    #[serde(default)]
+    pub nerd_conflicts: ConflictPolicy,
+}
+
+impl Default for EventConfig {
+    fn default() -> Self {
+        Self {
+            name: "MOPAD".to_string(),
+            start_date: date(2025, 11, 28),
+            end_date: date(2025, 11, 30),
+            timezone: "Europe/Berlin".to_string(),
+            active_hours_start: time(8, 0, 0, 0),
+            active_hours_end: time(22, 0, 0, 0),
+            slot_minutes: 15,
+            location_conflicts: ConflictPolicy::Warn,
+            nerd_conflicts: ConflictPolicy::Warn,
+        }
+    }
+}
+
+/// The event with its bounds resolved in its timezone, as sent to clients.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Event {
+    #[serde(flatten)]
+    pub config: EventConfig,
+    /// Midnight at the start of the first day.
+    pub starts_at: SystemTime,
+    /// Midnight at the end of the last day.
+    pub ends_at: SystemTime,
+}
+
+impl Event {
+    /// Reads `event.json` from the storage directory, creating it with defaults if missing.
+    pub async fn load(path: &Path) -> eyre::Result<Self> {
+        let path = path.join(EVENT_FILE);
+        let existed = try_exists(&path)
+            .await
+            .wrap_err("failed to check if event.json exists")?;
+        let config: EventConfig = read_from_file_or_create_default(&path)
+            .await
+            .wrap_err("failed to read event.json")?;
+        if !existed {
+            warn!(
+                "Created {path} with a placeholder event, edit it to configure the actual event",
+                path = path.display()
+            );
+        }
+        let event = Self::resolve(config).wrap_err("invalid event.json")?;
+        if event.is_placeholder() {
+            warn!(
+                "{path} still describes the placeholder event {name} from {start_date} to \
+                 {end_date}, talks can be scheduled at any time and no schedules are proposed \
+                 until it is configured",
+                path = path.display(),
+                name = event.config.name,
+                start_date = event.config.start_date,
+                end_date = event.config.end_date,
+            );
+        }
+        Ok(event)
+    }
+
+    fn resolve(config: EventConfig) -> eyre::Result<Self> {
+        if config.end_date < config.start_date {
+            bail!("end_date is before start_date");
+        }
+        if config.active_hours_end <= config.active_hours_start {
+            bail!("active_hours_end is not after active_hours_start");
+        }
+        if config.slot_minutes == 0 {
+            bail!("slot_minutes must not be zero");
+        }
+        let timezone = TimeZone::get(&config.timezone)
+            .wrap_err_with(|| format!("unknown timezone {}", config.timezone))?;
+        let starts_at = config
+            .start_date
+            .to_zoned(timezone.clone())
+            .wrap_err("failed to resolve start_date")?
+            .timestamp()
+            .into();
+        let ends_at = config
+            .end_date
+            .tomorrow()
+            .and_then(|day_after| day_after.to_zoned(timezone))
+            .wrap_err("failed to resolve end_date")?
+            .timestamp()
+            .into();
+        Ok(Self {
+            config,
+            starts_at,
+            ends_at,
+        })
+    }
+
+    /// What happens to changes that introduce the conflict.
+    pub fn conflict_policy(&self, conflict: &Conflict) -> ConflictPolicy {
+        match conflict {
+            Conflict::Location { .. } => self.config.location_conflicts,
+            Conflict::Nerd { .. } => self.config.nerd_conflicts,
+        }
+    }
+
+    /// Whether `event.json` still holds the defaults it was created with, which do not describe
+    /// an actual event.
+    pub fn is_placeholder(&self) -> bool {
+        let placeholder = EventConfig::default();
+        self.config.name == placeholder.name
+            && self.config.start_date == placeholder.start_date
+            && self.config.end_date == placeholder.end_date
+    }
+
+    /// The active hours of each day of the event, in which the solver places talks.
+    pub fn active_windows(&self) -> eyre::Result<Vec<TimeWindow>> {
+        if self.is_placeholder() {
+            bail!("configure the event in event.json before proposing schedules");
+        }
+        let timezone = TimeZone::get(&self.config.timezone)
+            .wrap_err_with(|| format!("unknown timezone {}", self.config.timezone))?;
+        let mut windows = Vec::new();
+        let mut day = self.config.start_date;
+        while day <= self.config.end_date {
+            let resolve = |time: Time| -> eyre::Result<SystemTime> {
+                Ok(day
+                    .to_datetime(time)
+                    .to_zoned(timezone.clone())
+                    .wrap_err_with(|| format!("failed to resolve active hours of {day}"))?
+                    .timestamp()
+                    .into())
+            };
+            windows.push(TimeWindow {
+                start: resolve(self.config.active_hours_start)?,
+                end: resolve(self.config.active_hours_end)?,
+            });
+            day = day.tomorrow().wrap_err("failed to resolve end_date")?;
+        }
+        Ok(windows)
+    }
+
+    /// Granularity of start times in the scheduler.
+    pub fn slot(&self) -> Duration {
+        Duration::from_secs(u64::from(self.config.slot_minutes) * 60)
+    }
+
+    /// Fails if the talk is scheduled to take place outside of the event.
+    ///
+    /// While the event is the placeholder, talks may take place at any time.
+    pub fn check_schedule(&self, talk: &Talk) -> eyre::Result<()> {
+        let Some(scheduled_at) = talk.scheduled_at else {
+            return Ok(());
+        };
+        let Some(ends_at) = scheduled_at.checked_add(talk.duration) else {
+            bail!("talk {} would never end", talk.id);
+        };
+        if self.is_placeholder() {
+            return Ok(());
+        }
+        if scheduled_at < self.starts_at || ends_at > self.ends_at {
+            bail!(
+                "talk {} would take place outside of {} ({} to {})",
+                talk.id,
+                self.config.name,
+                self.config.start_date,
+                self.config.end_date
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+    use crate::test_fixtures::{talk, HOUR};
+
+    fn configured() -> Event {
+        Event::resolve(EventConfig {
+            name: "Configured".to_string(),
+            ..EventConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn placeholder_event_allows_talks_at_any_time() {
+        let event = Event::resolve(EventConfig::default()).unwrap();
+        assert!(event.is_placeholder());
+        assert!(event.check_schedule(&talk(1, Some(10), None, &[])).is_ok());
+        assert!(event.active_windows().is_err());
+    }
+
+    #[test]
+    fn configured_event_bounds_talks() {
+        let event = configured();
+        assert!(!event.is_placeholder());
+        let mut inside = talk(1, None, None, &[]);
+        inside.scheduled_at = Some(event.ends_at - HOUR);
+        assert!(event.check_schedule(&inside).is_ok());
+        let mut overlong = inside.clone();
+        overlong.duration = 2 * HOUR;
+        assert!(event.check_schedule(&overlong).is_err());
+        assert!(event.check_schedule(&talk(2, Some(10), None, &[])).is_err());
+    }
+
+    #[test]
+    fn talks_that_would_never_end_are_refused() {
+        let mut endless = talk(1, Some(10), None, &[]);
+        endless.duration = Duration::MAX;
+        for event in [
+            Event::resolve(EventConfig::default()).unwrap(),
+            configured(),
+        ] {
+            assert!(event.check_schedule(&endless).is_err());
+        }
+    }
+
+    #[test]
+    fn active_windows_are_in_the_timezone_of_the_event() {
+        let windows = configured().active_windows().unwrap();
+        // 08:00 to 22:00 in Europe/Berlin, which is an hour ahead of UTC in November
+        let starts: Vec<_> = windows.iter().map(|window| window.start).collect();
+        let ends: Vec<_> = windows.iter().map(|window| window.end).collect();
+        assert_eq!(
+            starts,
+            [at(1_764_313_200), at(1_764_399_600), at(1_764_486_000)]
+        );
+        assert_eq!(
+            ends,
+            [at(1_764_363_600), at(1_764_450_000), at(1_764_536_400)]
+        );
+    }
+}
@@ -1,19 +1,42 @@
-use std::sync::Arc;
+use std::{collections::BTreeSet, path::PathBuf, sync::Arc, time::Duration};
 
+use eyre::Context as _;
+use notify::{
+    event::{ModifyKind, RenameMode},
+    recommended_watcher, Event, EventKind, RecursiveMode, Watcher as _,
+};
 use tokio::{
     signal::unix::{signal, SignalKind},
-    sync::{broadcast, RwLock},
+    sync::{mpsc, RwLock},
+    time::timeout,
 };
 use tracing::{error, info};
 
 use crate::{
+    backup::Backups,
+    conflicts::find_conflicts,
+    event::{self, EVENT_FILE},
+    json_backend::HAND_EDITED_FILES,
     messages::Update,
     storage::{Storage, Talk},
+    updates::UpdatesSender,
 };
 
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(500);
+
+/// When a refresh backs up the storage before touching it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum BackupBefore {
+    /// Every refresh, as announced reloads are rare and deliberate.
+    EveryRefresh,
+    /// Only refreshes that found hand edits, as the server's own writes trigger refreshes too.
+    HandEdits,
+}
+
 pub async fn refresh_files_from_disk_on_signal(
     storage: Arc<RwLock<Storage>>,
-    updates_sender: broadcast::Sender<Update>,
+    updates_sender: UpdatesSender,
+    backups: Backups,
 ) {
     let mut received_signals =
         signal(SignalKind::user_defined1()).expect("failed to register SIGUSR1 handler");
@@ -21,62 +44,281 @@ pub async fn refresh_files_from_disk_on_signal(
     loop {
         received_signals.recv().await;
         info!("Received SIGUSR1, refreshing storage...");
+        refresh_storage(
+            &storage,
+            &updates_sender,
+            &backups,
+            BackupBefore::EveryRefresh,
+        )
+        .await;
+    }
+}
 
-        let mut storage = storage.write().await;
+/// Refreshes the storage whenever one of the hand-edited files in the storage directory changes.
+///
+/// Changes are debounced, so an editor writing a file in several steps causes one refresh. The
+/// server's own writes are ignored: they always go to a `.tmp` file that is renamed afterwards.
+/// Files the server writes on its own, like `tokens.json` and `drafts.json`, are not watched.
+pub async fn refresh_files_from_disk_on_change(
+    storage: Arc<RwLock<Storage>>,
+    updates_sender: UpdatesSender,
+    backups: Backups,
+) -> eyre::Result<()> {
+    let (events_sender, mut events_receiver) = mpsc::unbounded_channel();
+    let mut watcher = recommended_watcher(move |event| {
+        let _ = events_sender.send(event);
+    })
+    .wrap_err("failed to create file watcher")?;
+    let path = storage.read().await.path.clone();
+    watcher
+        .watch(&path, RecursiveMode::NonRecursive)
+        .wrap_err_with(|| format!("failed to watch {}", path.display()))?;
+    info!("Watching {} for changes", path.display());
 
-        let refreshed_storage = match Storage::load(storage.path.clone()).await {
-            Ok(storage) => storage,
+    while let Some(event) = events_receiver.recv().await {
+        let mut events = vec![event];
+        while let Ok(Some(event)) = timeout(DEBOUNCE_DURATION, events_receiver.recv()).await {
+            events.push(event);
+        }
+        let changed_files = changed_files(events);
+        if changed_files.is_empty() {
+            continue;
+        }
+        info!(
+            "Files {} changed, refreshing storage...",
+            changed_files
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        refresh_storage(&storage, &updates_sender, &backups, BackupBefore::HandEdits).await;
+    }
+
+    Ok(())
+}
+
+/// Hand-edited files that were changed by anything other than the server itself.
+fn changed_files(events: Vec<notify::Result<Event>>) -> BTreeSet<PathBuf> {
+    let is_temporary =
+        |path: &PathBuf| path.extension().is_some_and(|extension| extension == "tmp");
+    let is_hand_edited = |path: &PathBuf| {
+        path.file_name().is_some_and(|name| {
+            HAND_EDITED_FILES
+                .into_iter()
+                .chain([EVENT_FILE])
+                .any(|file| name == file)
+        })
+    };
+    let mut changed_files = BTreeSet::new();
+    let mut renamed_files = BTreeSet::new();
+    let mut written_by_server = BTreeSet::new();
+    for event in events {
+        let event = match event {
+            Ok(event) => event,
             Err(error) => {
-                error!("Failed to refresh storage: {error:#?}");
+                error!("Failed to watch storage directory: {error:#?}");
                 continue;
             }
         };
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let [from, to] = event.paths.as_slice() {
+                    if is_temporary(from) {
+                        written_by_server.insert(to.clone());
+                    } else {
+                        changed_files.insert(to.clone());
+                    }
+                }
+            }
+            // Also reported as one event with both paths where the platform can pair them
+            EventKind::Modify(ModifyKind::Name(_)) => renamed_files.extend(event.paths),
+            kind if kind.is_create() || kind.is_modify() || kind.is_remove() => {
+                changed_files.extend(event.paths)
+            }
+            _ => {}
+        }
+    }
+    changed_files
+        .into_iter()
+        .chain(renamed_files.difference(&written_by_server).cloned())
+        .filter(is_hand_edited)
+        .collect()
+}
 
-        storage.teams = refreshed_storage.teams;
-
-        if *refreshed_storage.users != *storage.users {
-            info!("Users changed, sending update...");
-            storage.users = refreshed_storage.users;
-            let _ = updates_sender.send(Update::Users {
-                users: storage
-                    .users
-                    .iter()
-                    .map(|(user_id, user)| (*user_id, user.into()))
-                    .collect(),
-            });
+async fn refresh_storage(
+    storage: &RwLock<Storage>,
+    updates_sender: &UpdatesSender,
+    backups: &Backups,
+    backup_before: BackupBefore,
+) {
+    let mut storage = storage.write().await;
+
+    if backup_before == BackupBefore::EveryRefresh && !back_up(&storage, backups).await {
+        return;
+    }
+
+    let refreshed_event = match event::Event::load(&storage.path).await {
+        Ok(event) => event,
+        Err(error) => {
+            error!("Failed to refresh storage: {error:#?}");
+            return;
+        }
+    };
+    let refreshed_collections = match storage.read_hand_edits().await {
+        Ok(collections) => collections,
+        Err(error) => {
+            error!("Failed to refresh storage: {error:#?}");
+            return;
         }
+    };
+    let Some(refreshed_storage) = refreshed_collections else {
+        refresh_event(&mut storage, refreshed_event, updates_sender);
+        info!("No hand edits to the collections found");
+        return;
+    };
 
-        storage.locations = refreshed_storage.locations;
+    if backup_before == BackupBefore::HandEdits && !back_up(&storage, backups).await {
+        return;
+    }
 
-        for talk_id in storage
-            .talks
-            .keys()
-            .filter(|talk_id| !refreshed_storage.talks.contains_key(talk_id))
-        {
-            info!("Talk {talk_id} removed");
-            let _ = updates_sender.send(Update::RemoveTalk { talk_id: *talk_id });
+    let conflicts_before = find_conflicts(&storage.talks);
+    for team in storage.teams.difference(&refreshed_storage.teams) {
+        info!("Team {team} removed");
+        let _ = updates_sender.send(Update::RemoveTeam { team: team.clone() });
+    }
+    for team in refreshed_storage.teams.difference(&storage.teams) {
+        info!("Team {team} added");
+        let _ = updates_sender.send(Update::AddTeam { team: team.clone() });
+    }
+    storage.teams = refreshed_storage.teams;
+
+    if refreshed_storage.users != storage.users {
+        info!("Users changed, sending update...");
+        for (user_id, refreshed_user) in refreshed_storage.users.iter() {
+            if storage
+                .users
+                .get(user_id)
+                .is_some_and(|user| user.roles != refreshed_user.roles)
+            {
+                info!("Roles of user {user_id} changed");
+                let _ = updates_sender.send(Update::UpdateRoles {
+                    user_id: *user_id,
+                    roles: refreshed_user.roles.clone(),
+                });
+            }
         }
-        for (talk_id, refreshed_talk) in refreshed_storage.talks.iter() {
-            if let Some(existing_talk) = storage.talks.get(talk_id) {
-                update_existing_talk(existing_talk, refreshed_talk, &updates_sender, *talk_id);
-            } else {
-                info!("Talk {talk_id} added");
-                let _ = updates_sender.send(Update::AddTalk {
-                    talk: refreshed_talk.clone(),
+        storage.users = refreshed_storage.users;
+        let _ = updates_sender.send(Update::Users {
+            users: storage
+                .users
+                .iter()
+                .map(|(user_id, user)| (*user_id, user.into()))
+                .collect(),
+        });
+    }
+
+    for location_id in storage
+        .locations
+        .keys()
+        .filter(|location_id| !refreshed_storage.locations.contains_key(location_id))
+    {
+        info!("Location {location_id} removed");
+        let _ = updates_sender.send(Update::RemoveLocation {
+            location_id: *location_id,
+        });
+    }
+    for (location_id, refreshed_location) in refreshed_storage.locations.iter() {
+        match storage.locations.get(location_id) {
+            Some(existing_location) if existing_location == refreshed_location => {}
+            Some(_) => {
+                info!("Location {location_id} changed");
+                let _ = updates_sender.send(Update::ChangeLocation {
+                    location: refreshed_location.clone(),
+                });
+            }
+            None => {
+                info!("Location {location_id} added");
+                let _ = updates_sender.send(Update::AddLocation {
+                    location: refreshed_location.clone(),
                 });
             }
         }
-        storage.talks = refreshed_storage.talks;
-        info!("Storage refreshed");
+    }
+    storage.locations = refreshed_storage.locations;
+
+    for talk_id in storage
+        .talks
+        .keys()
+        .filter(|talk_id| !refreshed_storage.talks.contains_key(talk_id))
+    {
+        info!("Talk {talk_id} removed");
+        let _ = updates_sender.send(Update::RemoveTalk { talk_id: *talk_id });
+    }
+    for (talk_id, refreshed_talk) in refreshed_storage.talks.iter() {
+        if let Some(existing_talk) = storage.talks.get(talk_id) {
+            update_existing_talk(existing_talk, refreshed_talk, updates_sender, *talk_id);
+        } else {
+            info!("Talk {talk_id} added");
+            let _ = updates_sender.send(Update::AddTalk {
+                talk: refreshed_talk.clone(),
+            });
+        }
+    }
+    storage.talks = refreshed_storage.talks;
+    refresh_event(&mut storage, refreshed_event, updates_sender);
+    let conflicts = find_conflicts(&storage.talks);
+    if conflicts != conflicts_before {
+        let _ = updates_sender.send(Update::Conflicts { conflicts });
+    }
+    // The journal must not be replayed over the hand edits on the next start.
+    if let Err(error) = storage.store_snapshot().await {
+        error!("Failed to store refreshed storage: {error:#?}");
+    }
+    info!("Storage refreshed");
+}
+
+/// Backs up the storage before a refresh, returning whether the refresh may go ahead.
+async fn back_up(storage: &Storage, backups: &Backups) -> bool {
+    match backups.create(&storage.path).await {
+        Ok(name) => {
+            info!("Created backup {name} before refreshing");
+            true
+        }
+        Err(error) => {
+            error!("Failed to create backup, not refreshing storage: {error:#?}");
+            false
+        }
+    }
+}
+
+fn refresh_event(
+    storage: &mut Storage,
+    refreshed_event: event::Event,
+    updates_sender: &UpdatesSender,
+) {
+    if storage.event != refreshed_event {
+        info!("Event changed");
+        storage.event = refreshed_event;
+        let _ = updates_sender.send(Update::UpdateEvent {
+            event: storage.event.clone(),
+        });
     }
 }
 
 fn update_existing_talk(
     existing_talk: &Talk,
     refreshed_talk: &Talk,
-    updates_sender: &broadcast::Sender<Update>,
+    updates_sender: &UpdatesSender,
     talk_id: usize,
 ) {
+    if refreshed_talk.creator != existing_talk.creator {
+        info!("Talk {talk_id} creator changed");
+        let _ = updates_sender.send(Update::UpdateCreator {
+            talk_id,
+            creator: refreshed_talk.creator,
+        });
+    }
     if refreshed_talk.title != existing_talk.title {
         info!("Talk {talk_id} title changed");
         let _ = updates_sender.send(Update::UpdateTitle {
@@ -91,6 +333,13 @@ fn update_existing_talk(
             description: refreshed_talk.description.clone(),
         });
     }
+    if refreshed_talk.highlight != existing_talk.highlight {
+        info!("Talk {talk_id} highlight changed");
+        let _ = updates_sender.send(Update::UpdateHighlight {
+            talk_id,
+            highlight: refreshed_talk.highlight,
+        });
+    }
     if refreshed_talk.scheduled_at != existing_talk.scheduled_at {
         info!("Talk {talk_id} scheduled_at changed");
         let _ = updates_sender.send(Update::UpdateScheduledAt {
@@ -105,6 +354,13 @@ fn update_existing_talk(
             duration: refreshed_talk.duration,
         });
     }
+    if refreshed_talk.location != existing_talk.location {
+        info!("Talk {talk_id} location changed");
+        let _ = updates_sender.send(Update::UpdateLocation {
+            talk_id,
+            location: refreshed_talk.location,
+        });
+    }
     for user_id in existing_talk
         .noobs
         .iter()
@@ -122,12 +378,10 @@ fn update_existing_talk(
         .filter(|user_id| !existing_talk.noobs.contains(user_id))
     {
         info!("Talk {talk_id} noob {user_id} added");
-        if !existing_talk.noobs.contains(user_id) {
-            let _ = updates_sender.send(Update::AddNoob {
-                talk_id,
-                user_id: *user_id,
-            });
-        }
+        let _ = updates_sender.send(Update::AddNoob {
+            talk_id,
+            user_id: *user_id,
+        });
     }
     for user_id in existing_talk
         .nerds
@@ -146,11 +400,9 @@ fn update_existing_talk(
         .filter(|user_id| !existing_talk.nerds.contains(user_id))
     {
         info!("Talk {talk_id} nerd {user_id} added");
-        if !existing_talk.nerds.contains(user_id) {
-            let _ = updates_sender.send(Update::AddNerd {
-                talk_id,
-                user_id: *user_id,
-            });
-        }
+        let _ = updates_sender.send(Update::AddNerd {
+            talk_id,
+            user_id: *user_id,
+        });
     }
 }
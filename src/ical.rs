@@ -5,8 +5,8 @@ use axum::{
     http::{header::CONTENT_TYPE, StatusCode},
     response::IntoResponse,
 };
+use jiff::Timestamp;
 use serde::Deserialize;
-use time::{format_description::parse, OffsetDateTime};
 
 use crate::service::Service;
 
@@ -19,10 +19,11 @@ pub async fn handle_icalendar(
     State(service): State<Service>,
     parameters: Query<ICalendarParameters>,
 ) -> impl IntoResponse {
-    let mut response = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//HULKs//mopad//EN\r\nNAME:MOPAD\r\nX-WR-CALNAME:MOPAD\r\nX-WR-CALDESC:Moderated Organization PAD (powerful, agile, distributed)\r\n".to_string();
-    let format = parse("[year][month][day]T[hour][minute][second]Z").unwrap();
-    let now = OffsetDateTime::now_utc();
     let storage = service.storage.read().await;
+    let event = &storage.event.config;
+    let name = event.name.replace(['\r', '\n'], "");
+    let mut response = format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//HULKs//mopad//EN\r\nNAME:{name}\r\nX-WR-CALNAME:{name}\r\nX-WR-CALDESC:Moderated Organization PAD (powerful, agile, distributed)\r\nX-WR-TIMEZONE:{}\r\n", event.timezone);
+    let now = format_time(Timestamp::now());
     for talk in storage.talks.values() {
         match parameters.user_id {
             Some(user_id) if !talk.noobs.contains(&user_id) && !talk.nerds.contains(&user_id) => {
@@ -31,15 +32,21 @@ pub async fn handle_icalendar(
             _ => {}
         }
         if let Some(scheduled_at) = talk.scheduled_at {
-            let start = OffsetDateTime::from(scheduled_at);
-            let end = start + talk.duration;
+            let Some((start, end)) = scheduled_at.checked_add(talk.duration).and_then(|end| {
+                Some((
+                    Timestamp::try_from(scheduled_at).ok()?,
+                    Timestamp::try_from(end).ok()?,
+                ))
+            }) else {
+                continue;
+            };
             write!(
                 response,
                 "BEGIN:VEVENT\r\nUID:{}\r\nDTSTAMP:{}\r\nDTSTART:{}\r\nDTEND:{}\r\nSUMMARY:{}\r\nDESCRIPTION:{}\r\n",
                 talk.id,
-                now.format(&format).unwrap(),
-                start.format(&format).unwrap(),
-                end.format(&format).unwrap(),
+                now,
+                format_time(start),
+                format_time(end),
                 talk.title.replace(['\r', '\n'], ""),
                 talk.description.replace(['\r', '\n'], ""),
             )
@@ -86,3 +93,8 @@ pub async fn handle_icalendar(
         response,
     )
 }
+
+/// Formats the timestamp as iCalendar date-time in UTC.
+fn format_time(timestamp: Timestamp) -> String {
+    timestamp.strftime("%Y%m%dT%H%M%SZ").to_string()
+}
@@ -0,0 +1,178 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::Context as _;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    fs::{create_dir_all, read, rename, try_exists, File, OpenOptions},
+    io::AsyncWriteExt as _,
+    sync::RwLock,
+    time::interval,
+};
+use tracing::{error, warn};
+
+use crate::{
+    messages::Update,
+    storage::{Storage, UserId},
+};
+
+pub const JOURNAL_FILE: &str = "journal.jsonl";
+pub const ARCHIVE_DIRECTORY: &str = "journal";
+
+/// A single applied `Update` together with when and by whom it was applied.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JournalEntry {
+    pub timestamp: SystemTime,
+    pub user_id: Option<UserId>,
+    pub update: Update,
+}
+
+/// Append-only log of updates that were applied since the last snapshot.
+///
+/// Every entry is one JSON line in `journal.jsonl`. On compaction the file is moved into the
+/// `journal/` directory, named after the time of the compaction, so the full history is kept.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    archive_path: PathBuf,
+}
+
+impl Journal {
+    pub fn new(storage_path: &Path) -> Self {
+        Self {
+            path: storage_path.join(JOURNAL_FILE),
+            archive_path: storage_path.join(ARCHIVE_DIRECTORY),
+        }
+    }
+
+    pub async fn append(&self, entry: &JournalEntry) -> eyre::Result<()> {
+        let mut line = serde_json::to_vec(entry).wrap_err("failed to serialize journal entry")?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .wrap_err("failed to open journal")?;
+        file.write_all(&line)
+            .await
+            .wrap_err("failed to append to journal")?;
+        file.sync_data()
+            .await
+            .wrap_err("failed to sync journal to disk")
+    }
+
+    pub async fn read(&self) -> eyre::Result<Vec<JournalEntry>> {
+        self.read_lines().await
+    }
+
+    /// Reads all entries as plain JSON values, for migrations of their layout.
+    pub async fn read_raw(&self) -> eyre::Result<Vec<Value>> {
+        self.read_lines().await
+    }
+
+    async fn read_lines<T>(&self) -> eyre::Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        if !try_exists(&self.path)
+            .await
+            .wrap_err("failed to check if journal exists")?
+        {
+            return Ok(Vec::new());
+        }
+        let contents = read(&self.path).await.wrap_err("failed to read journal")?;
+        let mut entries = Vec::new();
+        for (index, line) in contents.split(|byte| *byte == b'\n').enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_slice(line) {
+                Ok(entry) => entries.push(entry),
+                Err(error) => warn!("Skipping unreadable journal line {}: {error}", index + 1),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Atomically replaces the journal with the given raw entries.
+    pub async fn rewrite_raw(&self, entries: &[Value]) -> eyre::Result<()> {
+        if entries.is_empty()
+            && !try_exists(&self.path)
+                .await
+                .wrap_err("failed to check if journal exists")?
+        {
+            return Ok(());
+        }
+        let mut contents = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut contents, entry)
+                .wrap_err("failed to serialize journal entry")?;
+            contents.push(b'\n');
+        }
+        let temp_path = self.path.with_extension("tmp");
+        let mut file = File::create(&temp_path)
+            .await
+            .wrap_err("failed to create temporary journal")?;
+        file.write_all(&contents)
+            .await
+            .wrap_err("failed to write temporary journal")?;
+        file.sync_all()
+            .await
+            .wrap_err("failed to sync temporary journal to disk")?;
+        rename(&temp_path, &self.path)
+            .await
+            .wrap_err("failed to replace journal")
+    }
+
+    pub async fn is_empty(&self) -> eyre::Result<bool> {
+        if !try_exists(&self.path)
+            .await
+            .wrap_err("failed to check if journal exists")?
+        {
+            return Ok(true);
+        }
+        let metadata = tokio::fs::metadata(&self.path)
+            .await
+            .wrap_err("failed to read journal metadata")?;
+        Ok(metadata.len() == 0)
+    }
+
+    /// Moves the current journal into the archive directory, starting a fresh one.
+    pub async fn archive(&self, now: SystemTime) -> eyre::Result<()> {
+        create_dir_all(&self.archive_path)
+            .await
+            .wrap_err("failed to create journal archive directory")?;
+        let seconds = now
+            .duration_since(UNIX_EPOCH)
+            .wrap_err("system time is before the UNIX epoch")?
+            .as_secs();
+        let mut archive = self.archive_path.join(format!("{seconds}.jsonl"));
+        let mut suffix = 1;
+        while try_exists(&archive)
+            .await
+            .wrap_err("failed to check if archived journal exists")?
+        {
+            archive = self.archive_path.join(format!("{seconds}-{suffix}.jsonl"));
+            suffix += 1;
+        }
+        rename(&self.path, archive)
+            .await
+            .wrap_err("failed to move journal into archive")
+    }
+}
+
+pub async fn compact_journal_periodically(storage: Arc<RwLock<Storage>>, period: Duration) {
+    let mut ticks = interval(period);
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        if let Err(error) = storage.write().await.compact().await {
+            error!("Failed to compact journal: {error:#?}");
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use eyre::Context as _;
+use serde_json::Value;
+
+use crate::{
+    json_file::{read_from_file_or_create_default, WriteToFileExt as _},
+    storage::{Draft, DraftName, InviteData, InviteId, TokenStore, User, UserId},
+    storage_backend::{RawCollections, StorageBackend},
+};
+
+pub const TEAMS_FILE: &str = "teams.json";
+const USERS_FILE: &str = "users.json";
+const LOCATIONS_FILE: &str = "locations.json";
+const TALKS_FILE: &str = "talks.json";
+const TOKENS_FILE: &str = "tokens.json";
+const INVITES_FILE: &str = "invites.json";
+const DRAFTS_FILE: &str = "drafts.json";
+
+pub const COLLECTION_FILES: [&str; 7] = [
+    TEAMS_FILE,
+    USERS_FILE,
+    LOCATIONS_FILE,
+    TALKS_FILE,
+    TOKENS_FILE,
+    INVITES_FILE,
+    DRAFTS_FILE,
+];
+
+/// Files of the collections that are edited by hand, the others are only written by the server.
+pub const HAND_EDITED_FILES: [&str; 4] = [TEAMS_FILE, USERS_FILE, LOCATIONS_FILE, TALKS_FILE];
+
+/// Stores every collection in its own JSON file inside the storage directory.
+#[derive(Debug)]
+pub struct JsonBackend {
+    path: PathBuf,
+}
+
+impl JsonBackend {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl StorageBackend for JsonBackend {
+    async fn load_raw(&self) -> eyre::Result<RawCollections> {
+        let teams = read_from_file_or_create_default(self.path.join(TEAMS_FILE))
+            .await
+            .wrap_err("failed to read teams.json")?;
+        let users = read_from_file_or_create_default(self.path.join(USERS_FILE))
+            .await
+            .wrap_err("failed to read users")?;
+        let locations = read_from_file_or_create_default(self.path.join(LOCATIONS_FILE)).await?;
+        let talks = read_from_file_or_create_default(self.path.join(TALKS_FILE)).await?;
+        let tokens = read_from_file_or_create_default(self.path.join(TOKENS_FILE)).await?;
+        let invites = read_from_file_or_create_default(self.path.join(INVITES_FILE)).await?;
+        let drafts = read_from_file_or_create_default(self.path.join(DRAFTS_FILE)).await?;
+        Ok(RawCollections {
+            teams,
+            users,
+            locations,
+            talks,
+            tokens,
+            invites,
+            drafts,
+        })
+    }
+
+    async fn store_user(
+        &self,
+        users: &BTreeMap<UserId, User>,
+        user_id: UserId,
+    ) -> eyre::Result<()> {
+        let path = self.path.join(USERS_FILE);
+        let mut stored_users: BTreeMap<UserId, Value> = read_from_file_or_create_default(&path)
+            .await
+            .wrap_err("failed to read users.json")?;
+        match users.get(&user_id) {
+            Some(user) => {
+                stored_users.insert(
+                    user_id,
+                    serde_json::to_value(user).wrap_err("failed to serialize user")?,
+                );
+            }
+            None => {
+                stored_users.remove(&user_id);
+            }
+        }
+        stored_users
+            .write_to_file(path)
+            .await
+            .wrap_err("failed to write users.json")
+    }
+
+    async fn store_tokens(&self, tokens: &TokenStore) -> eyre::Result<()> {
+        tokens
+            .write_to_file(self.path.join(TOKENS_FILE))
+            .await
+            .wrap_err("failed to write tokens.json")
+    }
+
+    async fn store_invites(&self, invites: &BTreeMap<InviteId, InviteData>) -> eyre::Result<()> {
+        invites
+            .write_to_file(self.path.join(INVITES_FILE))
+            .await
+            .wrap_err("failed to write invites.json")
+    }
+
+    async fn store_drafts(&self, drafts: &BTreeMap<DraftName, Draft>) -> eyre::Result<()> {
+        drafts
+            .write_to_file(self.path.join(DRAFTS_FILE))
+            .await
+            .wrap_err("failed to write drafts.json")
+    }
+
+    async fn store_raw(&self, collections: &RawCollections) -> eyre::Result<()> {
+        collections
+            .teams
+            .write_to_file(self.path.join(TEAMS_FILE))
+            .await
+            .wrap_err("failed to write teams.json")?;
+        collections
+            .users
+            .write_to_file(self.path.join(USERS_FILE))
+            .await
+            .wrap_err("failed to write users.json")?;
+        collections
+            .locations
+            .write_to_file(self.path.join(LOCATIONS_FILE))
+            .await
+            .wrap_err("failed to write locations.json")?;
+        collections
+            .talks
+            .write_to_file(self.path.join(TALKS_FILE))
+            .await
+            .wrap_err("failed to write talks.json")?;
+        collections
+            .tokens
+            .write_to_file(self.path.join(TOKENS_FILE))
+            .await
+            .wrap_err("failed to write tokens.json")?;
+        collections
+            .invites
+            .write_to_file(self.path.join(INVITES_FILE))
+            .await
+            .wrap_err("failed to write invites.json")?;
+        collections
+            .drafts
+            .write_to_file(self.path.join(DRAFTS_FILE))
+            .await
+            .wrap_err("failed to write drafts.json")
+    }
+}
@@ -1,8 +1,5 @@
 use core::fmt::Debug;
-use std::{
-    ops::{Deref, DerefMut},
-    path::{Path, PathBuf},
-};
+use std::path::Path;
 
 use eyre::Context as _;
 use serde::{Deserialize, Serialize};
@@ -12,62 +9,24 @@ use tokio::{
 };
 use tracing::warn;
 
-#[derive(Debug)]
-pub struct MirroredToDisk<T> {
-    pub path: PathBuf,
-    pub value: T,
-}
-
-impl<T> Deref for MirroredToDisk<T> {
-    type Target = T;
-
-    fn deref(&self) -> &Self::Target {
-        &self.value
-    }
-}
-
-impl<T> DerefMut for MirroredToDisk<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.value
-    }
-}
-
-impl<T> MirroredToDisk<T>
+pub async fn read_from_file_or_create_default<T>(path: impl AsRef<Path> + Debug) -> eyre::Result<T>
 where
-    T: ReadFromFileExt + WriteToFileExt,
+    T: ReadFromFileExt + WriteToFileExt + Default,
 {
-    pub async fn read_from(path: impl Into<PathBuf>) -> eyre::Result<Self> {
-        let path = path.into();
-        let value = T::read_from_file(&path).await?;
-        Ok(Self { path, value })
-    }
-
-    pub async fn read_from_or_create_default(path: impl Into<PathBuf>) -> eyre::Result<Self>
-    where
-        T: Default,
-    {
-        let path = path.into();
-        if try_exists(&path)
-            .await
-            .wrap_err("failed to check if file exists")?
-        {
-            Self::read_from(path).await
-        } else {
-            warn!(
-                "Cannot find file {path}, creating default",
-                path = path.display()
-            );
-            let value = T::default();
-            value.write_to_file(&path).await?;
-            Ok(Self { path, value })
-        }
-    }
-
-    pub async fn commit(&self) -> eyre::Result<()>
-    where
-        T: Serialize,
+    let path = path.as_ref();
+    if try_exists(path)
+        .await
+        .wrap_err("failed to check if file exists")?
     {
-        self.value.write_to_file(&self.path).await
+        T::read_from_file(path).await
+    } else {
+        warn!(
+            "Cannot find file {path}, creating default",
+            path = path.display()
+        );
+        let value = T::default();
+        value.write_to_file(path).await?;
+        Ok(value)
     }
 }
 
@@ -1,37 +1,55 @@
-use std::{net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc};
+use std::{net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc, time::Duration};
 
 use axum::{
     extract::State,
     routing::{get, get_service},
     Json, Router,
 };
-use clap::Parser;
-use client::handle_websocket;
+use backup::{back_up_periodically, Backups};
+use clap::{Parser, Subcommand};
+use client::{handle_websocket, Heartbeat};
 use eyre::WrapErr;
-use file_watch::refresh_files_from_disk_on_signal;
+use file_watch::{refresh_files_from_disk_on_change, refresh_files_from_disk_on_signal};
 use ical::handle_icalendar;
+use journal::compact_journal_periodically;
 use storage::Storage;
+use storage_backend::BackendKind;
 use tokio::{
     signal, spawn,
     sync::{broadcast, RwLock},
 };
 use tower_http::services::ServeDir;
-use tracing::info;
+use tracing::{error, info, warn};
+use updates::UpdatesSender;
 
-use crate::service::Service;
+use crate::service::{RegistrationMode, Service, TokenLifetime};
 
+mod backup;
 mod client;
+mod conflicts;
+mod event;
 mod file_watch;
 mod ical;
+mod journal;
+mod json_backend;
+mod json_file;
 mod messages;
-mod mirrored_to_disk;
+mod migration;
+mod rate_limit;
 mod service;
+mod solver;
+mod sqlite_backend;
 mod storage;
+mod storage_backend;
+#[cfg(test)]
+mod test_fixtures;
+mod updates;
 
 const INTERNAL_CHANNEL_CAPACITY: usize = 1337;
 const API_ENDPOINT: &str = "/api";
 const TEAM_ENDPOINT: &str = "/teams.json";
 const LOCATION_ENDPOINT: &str = "/locations.json";
+const EVENT_ENDPOINT: &str = "/event.json";
 const ICAL_ENDPOINT: &str = "/talks.ics";
 
 /// Moderated Organization PAD (powerful, agile, distributed)
@@ -43,6 +61,12 @@ struct Arguments {
     /// Path to the storage directory.
     #[clap(long, default_value = "storage/")]
     storage: PathBuf,
+    /// Backend used to persist the storage.
+    #[clap(long, value_enum, default_value_t = BackendKind::Json)]
+    backend: BackendKind,
+    /// Interval in seconds between compactions of the journal into a storage snapshot.
+    #[clap(long, default_value = "600", value_parser = clap::value_parser!(u64).range(1..))]
+    compaction_interval: u64,
     /// Port to listen on.
     #[clap(long, default_value = "9559")]
     port: u16,
@@ -52,6 +76,50 @@ struct Arguments {
     /// Path to the frontend directory.
     #[clap(long, default_value = "frontend/")]
     frontend: String,
+    /// Also refresh the storage when its JSON files change, not only on SIGUSR1. Only for the JSON
+    /// backend.
+    #[clap(long)]
+    watch_storage: bool,
+    /// Path to the backup directory [default: <STORAGE>/backups/].
+    #[clap(long)]
+    backup_directory: Option<PathBuf>,
+    /// Interval in seconds between backups of the storage directory.
+    #[clap(long, default_value = "3600", value_parser = clap::value_parser!(u64).range(1..))]
+    backup_interval: u64,
+    /// Number of backups to keep, older ones are removed.
+    #[clap(long, default_value = "48")]
+    backup_retention: usize,
+    /// Number of recent updates kept for clients resuming after a reconnect.
+    #[clap(long, default_value = "1000")]
+    replay_buffer_size: usize,
+    /// Interval in seconds between pings sent to each connected client.
+    #[clap(long, default_value = "30")]
+    ping_interval: u64,
+    /// Seconds to wait for an answer to a ping before closing the connection.
+    #[clap(long, default_value = "10")]
+    ping_timeout: u64,
+    /// Seconds a login stays valid, renewed whenever the client reconnects.
+    #[clap(long, default_value = "604800")]
+    token_lifetime: u64,
+    /// Seconds after which a login expires even if it was renewed.
+    #[clap(long, default_value = "2592000")]
+    max_session_age: u64,
+    /// Who may register an account.
+    #[clap(long, value_enum, default_value_t = RegistrationMode::Open)]
+    registration: RegistrationMode,
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+    /// List the available backups of the storage directory, oldest first.
+    ListBackups,
+    /// Replace the storage directory with a backup. Stop the server before restoring.
+    RestoreBackup {
+        /// Name of the backup as printed by `list-backups`.
+        name: String,
+    },
 }
 
 #[tokio::main]
@@ -59,21 +127,86 @@ async fn main() -> eyre::Result<()> {
     let arguments = Arguments::parse();
     tracing_subscriber::fmt().init();
 
-    let storage = Storage::load(&arguments.storage)
+    let backups = Backups::new(
+        arguments
+            .backup_directory
+            .clone()
+            .unwrap_or_else(|| arguments.storage.join("backups")),
+        arguments.backup_retention,
+    );
+    match arguments.command {
+        Some(CliCommand::ListBackups) => {
+            for name in backups.list().await.wrap_err("failed to list backups")? {
+                println!("{name}");
+            }
+            return Ok(());
+        }
+        Some(CliCommand::RestoreBackup { name }) => {
+            let safety_backup = backups
+                .restore(&name, &arguments.storage)
+                .await
+                .wrap_err_with(|| format!("failed to restore backup {name}"))?;
+            info!("Restored backup {name}, previous storage was saved as backup {safety_backup}");
+            return Ok(());
+        }
+        None => {}
+    }
+
+    let storage = Storage::load(&arguments.storage, arguments.backend)
         .await
         .wrap_err("failed to load storage")?;
-    let (updates_sender, _updates_receiver) = broadcast::channel(INTERNAL_CHANNEL_CAPACITY);
+    let updates_sender =
+        UpdatesSender::new(INTERNAL_CHANNEL_CAPACITY, arguments.replay_buffer_size);
+    let (revocations_sender, _revocations_receiver) = broadcast::channel(INTERNAL_CHANNEL_CAPACITY);
     let service = Service {
         storage: Arc::new(RwLock::new(storage)),
         updates_sender,
+        heartbeat: Heartbeat {
+            interval: Duration::from_secs(arguments.ping_interval.max(1)),
+            timeout: Duration::from_secs(arguments.ping_timeout),
+        },
+        revocations_sender,
+        token_lifetime: TokenLifetime {
+            lifetime: Duration::from_secs(arguments.token_lifetime),
+            max_session_age: Duration::from_secs(arguments.max_session_age),
+        },
+        rate_limits: Default::default(),
+        registration_mode: arguments.registration,
     };
 
+    spawn(compact_journal_periodically(
+        service.storage.clone(),
+        Duration::from_secs(arguments.compaction_interval),
+    ));
+
+    spawn(back_up_periodically(
+        service.storage.clone(),
+        backups.clone(),
+        Duration::from_secs(arguments.backup_interval),
+    ));
+
+    if arguments.watch_storage && arguments.backend == BackendKind::Sqlite {
+        warn!("Not watching the storage, the SQLite backend is reloaded with SIGUSR1 only");
+    } else if arguments.watch_storage {
+        let storage = service.storage.clone();
+        let updates_sender = service.updates_sender.clone();
+        let backups = backups.clone();
+        spawn(async move {
+            if let Err(error) =
+                refresh_files_from_disk_on_change(storage, updates_sender, backups).await
+            {
+                error!("Failed to watch storage directory: {error:#?}");
+            }
+        });
+    }
+
     spawn({
         let storage = service.storage.clone();
         let updates_sender = service.updates_sender.clone();
-        refresh_files_from_disk_on_signal(storage, updates_sender)
+        refresh_files_from_disk_on_signal(storage, updates_sender, backups)
     });
 
+    let storage = service.storage.clone();
     let application = Router::new()
         .route(API_ENDPOINT, get(handle_websocket))
         .route(
@@ -88,6 +221,12 @@ async fn main() -> eyre::Result<()> {
                 Json(state.storage.read().await.locations.clone())
             }),
         )
+        .route(
+            EVENT_ENDPOINT,
+            get(move |State(state): State<Service>| async move {
+                Json(state.storage.read().await.event.clone())
+            }),
+        )
         .route(ICAL_ENDPOINT, get(handle_icalendar))
         .fallback(get_service(ServeDir::new(arguments.frontend)))
         .with_state(service);
@@ -100,10 +239,20 @@ async fn main() -> eyre::Result<()> {
     let listener = tokio::net::TcpListener::bind(bind_address)
         .await
         .wrap_err("failed to bind to address")?;
-    axum::serve(listener, application.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
+    axum::serve(
+        listener,
+        application.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .wrap_err("failed to serve application")?;
+
+    // Leave the files authoritative for hand edits while the server is stopped.
+    let mut storage = storage.write().await;
+    storage
+        .compact()
         .await
-        .wrap_err("failed to serve application")
+        .wrap_err("failed to compact journal on shutdown")
 }
 
 async fn shutdown_signal() {
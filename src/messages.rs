@@ -1,11 +1,21 @@
 use std::{
+    borrow::Cow,
     collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
     time::{Duration, SystemTime},
 };
 
 use serde::{Deserialize, Serialize};
 
-use crate::storage::{AttendanceMode, Role, Talk, User};
+use crate::{
+    conflicts::{visible_conflicts, Conflict},
+    event::Event,
+    solver::ScheduleProposal,
+    storage::{
+        AttendanceMode, Draft, DraftName, InviteId, Location, Role, Talk, TalkSchedule, User,
+        UserId,
+    },
+};
 
 /// Authentication command sent by the client.
 #[derive(Clone, Debug, Deserialize)]
@@ -15,6 +25,8 @@ pub enum AuthenticationCommand {
         team: String,
         attendance_mode: Option<AttendanceMode>,
         password: String,
+        /// Required if the server only allows registration with an invite code.
+        invite_code: Option<String>,
     },
     Login {
         name: String,
@@ -24,6 +36,18 @@ pub enum AuthenticationCommand {
     Relogin {
         token: String,
     },
+    /// Sets a new password with a reset token issued by an admin, then logs in.
+    ResetPassword {
+        name: String,
+        team: String,
+        reset_token: String,
+        new_password: String,
+    },
+    /// Like `Relogin`, but only sends the updates after `last_seq` instead of the full state.
+    Resume {
+        token: String,
+        last_seq: u64,
+    },
 }
 
 /// Server response to an authentication command.
@@ -39,7 +63,72 @@ pub enum AuthenticationResponse {
     },
 }
 
+/// Server response to a command, sent only to the client that issued it.
+#[derive(Clone, Debug, Serialize)]
+pub enum CommandResponse {
+    Ack {
+        request_id: u64,
+    },
+    Error {
+        request_id: Option<u64>,
+        reason: String,
+    },
+    /// The command succeeded, but the user should know about something, e.g. conflicts.
+    Warning {
+        request_id: Option<u64>,
+        warning: String,
+    },
+    Sessions {
+        request_id: Option<u64>,
+        sessions: Vec<Session>,
+    },
+    PasswordReset {
+        request_id: Option<u64>,
+        reset_token: String,
+        expires_at: SystemTime,
+    },
+    Invite {
+        request_id: Option<u64>,
+        invite_id: InviteId,
+        invite_code: String,
+    },
+    Invites {
+        request_id: Option<u64>,
+        invites: Vec<Invite>,
+    },
+    ScheduleProposal {
+        request_id: Option<u64>,
+        #[serde(flatten)]
+        proposal: ScheduleProposal,
+    },
+}
+
+/// An invite code of a team, as listed by `Command::ListInvites` without the code itself.
+#[derive(Clone, Debug, Serialize)]
+pub struct Invite {
+    pub invite_id: InviteId,
+    pub team: String,
+    pub created_by: UserId,
+    pub created_at: SystemTime,
+}
+
+/// An active login of a user, as listed by `Command::ListSessions`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Session {
+    pub session_id: String,
+    pub created_at: SystemTime,
+    pub last_used_at: SystemTime,
+    pub expires_at: SystemTime,
+    pub user_agent: Option<String>,
+    pub remote_address: Option<SocketAddr>,
+    /// Whether this is the session of the connection that listed the sessions.
+    pub current: bool,
+}
+
 /// Commands are sent from the client to the server to request changes.
+///
+/// Next to the command, the JSON object may contain a `request_id` chosen by the client, which is
+/// repeated in the `CommandResponse`.
 #[derive(Clone, Debug, Deserialize)]
 pub enum Command {
     AddTalk {
@@ -85,13 +174,125 @@ pub enum Command {
     SetAttendanceMode {
         attendance_mode: AttendanceMode,
     },
+    /// Revokes the token of this connection.
+    Logout {},
+    /// Revokes all tokens of the user, on all devices.
+    LogoutAll {},
+    ListSessions {},
+    /// Revokes a single session of the user, e.g. on a device that got lost.
+    RevokeSession {
+        session_id: String,
+    },
+    ChangePassword {
+        old: String,
+        new: String,
+    },
+    /// Issues a password reset token for another user, only allowed for admins.
+    IssuePasswordReset {
+        user_id: UserId,
+    },
+    /// Revokes all tokens of another user, only allowed for admins.
+    RevokeTokens {
+        user_id: UserId,
+    },
+    /// Gives a role to a user, only allowed for admins.
+    GrantRole {
+        user_id: UserId,
+        role: Role,
+    },
+    /// Takes a role from a user, only allowed for admins.
+    RevokeRole {
+        user_id: UserId,
+        role: Role,
+    },
+    /// Creates an account for someone else, only allowed for admins and possible even if
+    /// registration is closed.
+    CreateUser {
+        name: String,
+        team: String,
+        attendance_mode: Option<AttendanceMode>,
+        password: String,
+    },
+    /// Generates an invite code for registering into the team, only allowed for admins.
+    CreateInvite {
+        team: String,
+    },
+    ListInvites {},
+    RevokeInvite {
+        invite_id: InviteId,
+    },
+    /// Adds a team, only allowed for admins like all team commands.
+    CreateTeam {
+        team: String,
+    },
+    /// Renames a team, its users move along.
+    RenameTeam {
+        team: String,
+        new_name: String,
+    },
+    /// Moves all users of a team into another team and removes it.
+    MergeTeams {
+        team: String,
+        into: String,
+    },
+    /// Removes a team without users.
+    DeleteTeam {
+        team: String,
+    },
+    /// Adds a location, only allowed for schedulers like all location commands.
+    CreateLocation {
+        name: String,
+        live_stream: Option<String>,
+        capacity: Option<u32>,
+        building: Option<String>,
+        floor: Option<String>,
+        accessibility_notes: Option<String>,
+        map_url: Option<String>,
+    },
+    /// Replaces the location with the same id.
+    EditLocation {
+        location: Location,
+    },
+    /// Removes a location, talks taking place there lose their location.
+    DeleteLocation {
+        location_id: usize,
+    },
+    /// Proposes start times and locations for the unscheduled talks, without applying them.
+    ///
+    /// Talks are placed in the active hours of the event at its slot granularity.
+    ProposeSchedule {},
+    /// Plans the schedules of talks in the draft, creating the draft if it does not exist yet.
+    ///
+    /// Talks without a schedule keep their current schedule when the draft is published.
+    UpdateDraft {
+        name: DraftName,
+        schedules: BTreeMap<usize, Option<TalkSchedule>>,
+    },
+    DeleteDraft {
+        name: DraftName,
+    },
+    /// Applies all planned schedules of the draft at once and deletes it.
+    PublishDraft {
+        name: DraftName,
+    },
 }
 
 #[allow(clippy::enum_variant_names)]
 /// Update messages are sent from the server to the client to inform the client of changes to the
 /// state of the server.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Update {
+    /// The complete state, sent to a client only, when it connects or cannot resume.
+    Snapshot {
+        users: BTreeMap<usize, UserReference>,
+        talks: BTreeMap<usize, Talk>,
+        locations: BTreeMap<usize, Location>,
+        teams: BTreeSet<String>,
+        conflicts: BTreeSet<Conflict>,
+        /// Only filled for schedulers.
+        drafts: BTreeMap<DraftName, Draft>,
+        event: Event,
+    },
     Users {
         users: BTreeMap<usize, UserReference>,
     },
@@ -101,6 +302,10 @@ pub enum Update {
     RemoveTalk {
         talk_id: usize,
     },
+    UpdateCreator {
+        talk_id: usize,
+        creator: UserId,
+    },
     UpdateTitle {
         talk_id: usize,
         title: String,
@@ -109,6 +314,10 @@ pub enum Update {
         talk_id: usize,
         description: String,
     },
+    UpdateHighlight {
+        talk_id: usize,
+        highlight: bool,
+    },
     UpdateScheduledAt {
         talk_id: usize,
         scheduled_at: Option<SystemTime>,
@@ -121,6 +330,10 @@ pub enum Update {
         talk_id: usize,
         location: Option<usize>,
     },
+    /// Changes the schedules of several talks at once, e.g. when a draft is published.
+    Reschedule {
+        schedules: BTreeMap<usize, TalkSchedule>,
+    },
     AddNoob {
         talk_id: usize,
         user_id: usize,
@@ -141,6 +354,66 @@ pub enum Update {
         user_id: usize,
         attendance_mode: AttendanceMode,
     },
+    /// Only sent to the connections of the user, since roles are not part of `UserReference`.
+    UpdateRoles {
+        user_id: usize,
+        roles: BTreeSet<Role>,
+    },
+    AddLocation {
+        location: Location,
+    },
+    ChangeLocation {
+        location: Location,
+    },
+    RemoveLocation {
+        location_id: usize,
+    },
+    AddTeam {
+        team: String,
+    },
+    RemoveTeam {
+        team: String,
+    },
+    /// Replaces the team by `new_name` and moves its users along, `new_name` already exists when
+    /// teams are merged.
+    RenameTeam {
+        team: String,
+        new_name: String,
+    },
+    /// All conflicts in the schedule, sent whenever they change.
+    Conflicts {
+        conflicts: BTreeSet<Conflict>,
+    },
+    /// Only sent to schedulers, without a draft when it was deleted or published.
+    UpdateDraft {
+        name: DraftName,
+        draft: Option<Draft>,
+    },
+    /// Sent when `event.json` was changed on disk.
+    UpdateEvent {
+        event: Event,
+    },
+}
+
+impl Update {
+    /// The update as connections of the user with the roles receive it, if at all.
+    pub fn visible_to(&self, user_id: UserId, roles: &BTreeSet<Role>) -> Option<Cow<'_, Self>> {
+        match self {
+            Update::UpdateRoles {
+                user_id: target_user_id,
+                ..
+            } => (*target_user_id == user_id).then_some(Cow::Borrowed(self)),
+            Update::UpdateDraft { .. } => roles
+                .contains(&Role::Scheduler)
+                .then_some(Cow::Borrowed(self)),
+            Update::Conflicts { conflicts } if !roles.contains(&Role::Scheduler) => {
+                Some(Cow::Owned(Update::Conflicts {
+                    conflicts: visible_conflicts(conflicts, user_id, roles),
+                }))
+            }
+            _ => Some(Cow::Borrowed(self)),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -0,0 +1,464 @@
+use std::path::Path;
+
+use eyre::{bail, Context as _};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::fs::try_exists;
+use tracing::info;
+
+use crate::{
+    journal::Journal,
+    json_file::{ReadFromFileExt as _, WriteToFileExt as _},
+    storage::{generate_session_id, hash_token},
+    storage_backend::{Backend, RawCollections, StorageBackend as _},
+};
+
+pub const VERSION_FILE: &str = "version.json";
+
+/// Layout version written by this build. Storage without a `version.json` is at version 0.
+pub const CURRENT_VERSION: u32 = 5;
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Version {
+    version: u32,
+}
+
+struct Migration {
+    /// Version of the layout after the migration ran.
+    version: u32,
+    description: &'static str,
+    migrate: fn(&mut RawCollections, &mut [Value]) -> eyre::Result<()>,
+}
+
+/// Migrations in ascending order of their version, each upgrading from the version before.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "fill in fields added after the first release",
+        migrate: fill_in_missing_fields,
+    },
+    Migration {
+        version: 2,
+        description: "record session metadata of tokens",
+        migrate: add_session_metadata,
+    },
+    Migration {
+        version: 3,
+        description: "store hashes of tokens instead of the tokens",
+        migrate: hash_tokens,
+    },
+    Migration {
+        version: 4,
+        description: "add password resets to users",
+        migrate: add_password_resets,
+    },
+    Migration {
+        version: 5,
+        description: "add capacity and directions to locations",
+        migrate: add_location_details,
+    },
+];
+
+/// Upgrades the storage directory step by step to `CURRENT_VERSION`.
+///
+/// Refuses to touch storage written by a newer build, since its layout is unknown to us.
+pub async fn migrate(path: &Path, backend: &Backend, journal: &Journal) -> eyre::Result<()> {
+    let version_path = path.join(VERSION_FILE);
+    let version = if try_exists(&version_path)
+        .await
+        .wrap_err("failed to check if version.json exists")?
+    {
+        Version::read_from_file(&version_path)
+            .await
+            .wrap_err("failed to read version.json")?
+            .version
+    } else {
+        0
+    };
+
+    if version > CURRENT_VERSION {
+        bail!(
+            "storage has version {version}, but this build only supports up to version {CURRENT_VERSION}"
+        );
+    }
+    if version == CURRENT_VERSION {
+        return Ok(());
+    }
+
+    let mut collections = backend
+        .load_raw()
+        .await
+        .wrap_err("failed to load collections")?;
+    let mut entries = journal
+        .read_raw()
+        .await
+        .wrap_err("failed to read journal")?;
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > version)
+    {
+        info!(
+            "Migrating storage to version {}: {}",
+            migration.version, migration.description
+        );
+        (migration.migrate)(&mut collections, &mut entries)
+            .wrap_err_with(|| format!("failed to migrate to version {}", migration.version))?;
+    }
+    backend
+        .store_raw(&collections)
+        .await
+        .wrap_err("failed to store migrated collections")?;
+    journal
+        .rewrite_raw(&entries)
+        .await
+        .wrap_err("failed to store migrated journal")?;
+    Version {
+        version: CURRENT_VERSION,
+    }
+    .write_to_file(&version_path)
+    .await
+    .wrap_err("failed to write version.json")
+}
+
+/// Talks stored in the collections and talks added by journal entries.
+fn talks_mut<'a>(
+    collections: &'a mut RawCollections,
+    journal: &'a mut [Value],
+) -> impl Iterator<Item = &'a mut Value> {
+    collections.talks.values_mut().chain(
+        journal
+            .iter_mut()
+            .filter_map(|entry| entry.pointer_mut("/update/AddTalk/talk")),
+    )
+}
+
+/// Locations stored in the collections and locations added or changed by journal entries.
+fn locations_mut<'a>(
+    collections: &'a mut RawCollections,
+    journal: &'a mut [Value],
+) -> impl Iterator<Item = &'a mut Value> {
+    collections
+        .locations
+        .values_mut()
+        .chain(journal.iter_mut().filter_map(|entry| {
+            let update = entry.get_mut("update")?.as_object_mut()?;
+            let kind = ["AddLocation", "ChangeLocation"]
+                .into_iter()
+                .find(|kind| update.contains_key(*kind))?;
+            update.get_mut(kind)?.get_mut("location")
+        }))
+}
+
+fn insert_default(entry: &mut Value, key: &str, default: Value) -> eyre::Result<()> {
+    let Some(object) = entry.as_object_mut() else {
+        bail!("expected an object, got {entry}");
+    };
+    object.entry(key).or_insert(default);
+    Ok(())
+}
+
+fn fill_in_missing_fields(
+    collections: &mut RawCollections,
+    journal: &mut [Value],
+) -> eyre::Result<()> {
+    for user in collections.users.values_mut() {
+        insert_default(user, "attendance_mode", json!("OnSite"))?;
+        insert_default(user, "roles", json!([]))?;
+    }
+    for location in collections.locations.values_mut() {
+        insert_default(location, "live_stream", Value::Null)?;
+    }
+    for talk in talks_mut(collections, journal) {
+        insert_default(talk, "highlight", json!(false))?;
+        insert_default(talk, "location", Value::Null)?;
+    }
+    Ok(())
+}
+
+fn add_session_metadata(
+    collections: &mut RawCollections,
+    _journal: &mut [Value],
+) -> eyre::Result<()> {
+    // Tokens were issued for seven days, so their creation time can be derived from the expiry
+    let seven_days = 60 * 60 * 24 * 7;
+    for token in collections.tokens.values_mut() {
+        let Some(expires_at) = token.get("expires_at").cloned() else {
+            bail!("expected token with expires_at, got {token}");
+        };
+        let mut created_at = expires_at;
+        if let Some(seconds) = created_at.get("secs_since_epoch").and_then(Value::as_u64) {
+            created_at["secs_since_epoch"] = json!(seconds.saturating_sub(seven_days));
+        }
+        insert_default(token, "session_id", json!(generate_session_id()))?;
+        insert_default(token, "created_at", created_at.clone())?;
+        insert_default(token, "last_used_at", created_at)?;
+        insert_default(token, "user_agent", Value::Null)?;
+        insert_default(token, "remote_address", Value::Null)?;
+    }
+    Ok(())
+}
+
+fn hash_tokens(collections: &mut RawCollections, _journal: &mut [Value]) -> eyre::Result<()> {
+    collections.tokens = std::mem::take(&mut collections.tokens)
+        .into_iter()
+        .map(|(token, data)| (hash_token(&token), data))
+        .collect();
+    Ok(())
+}
+
+fn add_password_resets(
+    collections: &mut RawCollections,
+    _journal: &mut [Value],
+) -> eyre::Result<()> {
+    for user in collections.users.values_mut() {
+        insert_default(user, "password_reset", Value::Null)?;
+    }
+    Ok(())
+}
+
+fn add_location_details(
+    collections: &mut RawCollections,
+    journal: &mut [Value],
+) -> eyre::Result<()> {
+    for location in locations_mut(collections, journal) {
+        for key in [
+            "capacity",
+            "building",
+            "floor",
+            "accessibility_notes",
+            "map_url",
+        ] {
+            insert_default(location, key, Value::Null)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use tempfile::tempdir;
+    use tokio::fs::write;
+
+    use super::*;
+    use crate::{json_backend::JsonBackend, messages::Update, storage::AttendanceMode};
+
+    const EXPIRES_AT: u64 = 1_800_000_000;
+    const SEVEN_DAYS: u64 = 60 * 60 * 24 * 7;
+
+    /// A talk as written by the first release, before `highlight` and `location` existed.
+    fn first_release_talk(id: usize) -> Value {
+        json!({
+            "id": id,
+            "creator": 0,
+            "title": format!("Talk {id}"),
+            "description": "",
+            "scheduled_at": null,
+            "duration": {"secs": 3600, "nanos": 0},
+            "nerds": [0],
+            "noobs": [],
+        })
+    }
+
+    fn first_release_collections() -> RawCollections {
+        RawCollections {
+            teams: BTreeSet::from(["Alpha".to_string()]),
+            users: BTreeMap::from([(
+                0,
+                json!({"id": 0, "name": "a", "team": "Alpha", "hash": "hash"}),
+            )]),
+            locations: BTreeMap::from([(0, json!({"id": 0, "name": "Hall"}))]),
+            talks: BTreeMap::from([(1, first_release_talk(1))]),
+            tokens: BTreeMap::from([(
+                "secret-token".to_string(),
+                json!({
+                    "user_id": 0,
+                    "expires_at": {"secs_since_epoch": EXPIRES_AT, "nanos_since_epoch": 0},
+                }),
+            )]),
+            ..RawCollections::default()
+        }
+    }
+
+    fn first_release_journal() -> Vec<Value> {
+        vec![json!({
+            "timestamp": {"secs_since_epoch": EXPIRES_AT, "nanos_since_epoch": 0},
+            "user_id": 0,
+            "update": {"AddTalk": {"talk": first_release_talk(2)}},
+        })]
+    }
+
+    #[test]
+    fn version_1_fills_in_missing_fields() {
+        let mut collections = first_release_collections();
+        collections.users.get_mut(&0).unwrap()["roles"] = json!(["Scheduler"]);
+        let mut journal = first_release_journal();
+        fill_in_missing_fields(&mut collections, &mut journal).unwrap();
+        assert_eq!(collections.users[&0]["attendance_mode"], json!("OnSite"));
+        assert_eq!(collections.users[&0]["roles"], json!(["Scheduler"]));
+        assert_eq!(collections.locations[&0]["live_stream"], Value::Null);
+        for talk in [
+            &collections.talks[&1],
+            &journal[0]["update"]["AddTalk"]["talk"],
+        ] {
+            assert_eq!(talk["highlight"], json!(false));
+            assert_eq!(talk["location"], Value::Null);
+        }
+    }
+
+    #[test]
+    fn version_2_derives_session_metadata_from_the_expiry() {
+        let mut collections = first_release_collections();
+        add_session_metadata(&mut collections, &mut []).unwrap();
+        let token = &collections.tokens["secret-token"];
+        assert!(token["session_id"]
+            .as_str()
+            .is_some_and(|id| !id.is_empty()));
+        let created_at = json!({
+            "secs_since_epoch": EXPIRES_AT - SEVEN_DAYS,
+            "nanos_since_epoch": 0,
+        });
+        assert_eq!(token["created_at"], created_at);
+        assert_eq!(token["last_used_at"], created_at);
+        assert_eq!(token["user_agent"], Value::Null);
+        assert_eq!(token["remote_address"], Value::Null);
+    }
+
+    #[test]
+    fn version_3_keys_tokens_by_their_hash() {
+        let mut collections = first_release_collections();
+        let data = collections.tokens["secret-token"].clone();
+        hash_tokens(&mut collections, &mut []).unwrap();
+        assert_eq!(
+            collections.tokens,
+            BTreeMap::from([(hash_token("secret-token"), data)])
+        );
+    }
+
+    #[test]
+    fn version_4_adds_password_resets() {
+        let mut collections = first_release_collections();
+        add_password_resets(&mut collections, &mut []).unwrap();
+        assert_eq!(collections.users[&0]["password_reset"], Value::Null);
+    }
+
+    #[test]
+    fn version_5_adds_location_details() {
+        let mut collections = first_release_collections();
+        collections.locations.get_mut(&0).unwrap()["capacity"] = json!(40);
+        let mut journal = vec![json!({
+            "timestamp": {"secs_since_epoch": EXPIRES_AT, "nanos_since_epoch": 0},
+            "user_id": 0,
+            "update": {"ChangeLocation": {"location": {"id": 0, "name": "Hall"}}},
+        })];
+        add_location_details(&mut collections, &mut journal).unwrap();
+        assert_eq!(collections.locations[&0]["capacity"], json!(40));
+        assert_eq!(collections.locations[&0]["map_url"], Value::Null);
+        let changed = &journal[0]["update"]["ChangeLocation"]["location"];
+        assert_eq!(changed["capacity"], Value::Null);
+        assert_eq!(changed["accessibility_notes"], Value::Null);
+    }
+
+    async fn write_storage(
+        path: &Path,
+        collections: &RawCollections,
+        journal: &[Value],
+        version: Option<u32>,
+    ) {
+        let files = [
+            ("teams.json", json!(collections.teams)),
+            ("users.json", json!(collections.users)),
+            ("locations.json", json!(collections.locations)),
+            ("talks.json", json!(collections.talks)),
+            ("tokens.json", json!(collections.tokens)),
+        ];
+        for (file, contents) in files {
+            write(path.join(file), contents.to_string()).await.unwrap();
+        }
+        let journal: String = journal.iter().map(|entry| format!("{entry}\n")).collect();
+        write(path.join("journal.jsonl"), journal).await.unwrap();
+        if let Some(version) = version {
+            Version { version }
+                .write_to_file(path.join(VERSION_FILE))
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn first_release_migrates_to_the_current_version() {
+        let directory = tempdir().unwrap();
+        let path = directory.path();
+        let collections = first_release_collections();
+        write_storage(path, &collections, &first_release_journal(), None).await;
+        let backend = Backend::Json(JsonBackend::new(path));
+        let journal = Journal::new(path);
+
+        migrate(path, &backend, &journal).await.unwrap();
+
+        let version = Version::read_from_file(path.join(VERSION_FILE))
+            .await
+            .unwrap();
+        assert_eq!(version.version, CURRENT_VERSION);
+        let collections = backend.load_raw().await.unwrap().deserialize().unwrap();
+        assert_eq!(
+            collections.users[&0].attendance_mode,
+            AttendanceMode::OnSite
+        );
+        assert_eq!(collections.locations[&0].capacity, None);
+        assert!(!collections.talks[&1].highlight);
+        let (token_hash, token) = collections.tokens.iter().next().unwrap();
+        assert_eq!(token_hash, &hash_token("secret-token"));
+        assert_eq!(
+            token.created_at,
+            UNIX_EPOCH + Duration::from_secs(EXPIRES_AT - SEVEN_DAYS)
+        );
+        let entries = journal.read().await.unwrap();
+        assert!(matches!(
+            &entries[0].update,
+            Update::AddTalk { talk } if talk.id == 2 && talk.location.is_none()
+        ));
+    }
+
+    #[tokio::test]
+    async fn migration_continues_from_the_stored_version() {
+        let directory = tempdir().unwrap();
+        let path = directory.path();
+        let mut collections = first_release_collections();
+        let mut entries = first_release_journal();
+        for migration in MIGRATIONS.iter().take(3) {
+            (migration.migrate)(&mut collections, &mut entries).unwrap();
+        }
+        write_storage(path, &collections, &entries, Some(3)).await;
+        let backend = Backend::Json(JsonBackend::new(path));
+
+        migrate(path, &backend, &Journal::new(path)).await.unwrap();
+
+        let migrated = backend.load_raw().await.unwrap();
+        assert_eq!(
+            migrated.tokens.keys().collect::<Vec<_>>(),
+            [&hash_token("secret-token")]
+        );
+        assert_eq!(migrated.users[&0]["password_reset"], Value::Null);
+        assert_eq!(migrated.locations[&0]["capacity"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn storage_of_a_newer_version_is_refused() {
+        let directory = tempdir().unwrap();
+        let path = directory.path();
+        write_storage(
+            path,
+            &RawCollections::default(),
+            &[],
+            Some(CURRENT_VERSION + 1),
+        )
+        .await;
+        let backend = Backend::Json(JsonBackend::new(path));
+        assert!(migrate(path, &backend, &Journal::new(path)).await.is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use eyre::bail;
+
+use crate::storage::UserId;
+
+/// Delay after the first attempt beyond the free ones, doubled with each further attempt.
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(15 * 60);
+/// Attempts are forgotten after this long without another attempt.
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+/// Number of tracked keys above which forgotten attempts are removed.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Limits of authentication attempts, shared by all connections.
+#[derive(Debug)]
+pub struct RateLimits {
+    /// Failed logins and password resets per remote address.
+    pub addresses: RateLimiter<IpAddr>,
+    /// Failed logins per account, identified by name and team, to slow down guessing a password
+    /// from many addresses. Password resets are not limited per account, so that an admin can
+    /// still let the owner in while the account is throttled.
+    pub accounts: RateLimiter<(String, String)>,
+    /// All registrations per remote address, so that nobody can create accounts in bulk.
+    pub registrations: RateLimiter<IpAddr>,
+    /// Registrations with invalid invite codes per remote address, to slow down guessing codes.
+    pub invite_codes: RateLimiter<IpAddr>,
+    /// Failed password changes per user.
+    pub password_changes: RateLimiter<UserId>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            // Generous, since many attendees may share the address of the venue network
+            addresses: RateLimiter::new(20),
+            accounts: RateLimiter::new(5),
+            // Generous as well, since attendees at the venue register from the same address
+            registrations: RateLimiter::new(20),
+            invite_codes: RateLimiter::new(5),
+            password_changes: RateLimiter::new(5),
+        }
+    }
+}
+
+/// Counts attempts per key and requires an exponentially growing delay once the free attempts
+/// are used up.
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    free_attempts: u32,
+    attempts: Mutex<HashMap<K, Attempts>>,
+}
+
+#[derive(Debug)]
+struct Attempts {
+    count: u32,
+    last_attempt: Instant,
+}
+
+impl<K> RateLimiter<K>
+where
+    K: Eq + Hash,
+{
+    pub fn new(free_attempts: u32) -> Self {
+        Self {
+            free_attempts,
+            attempts: Default::default(),
+        }
+    }
+
+    /// Counts an attempt, failing if the key has to wait before trying again.
+    ///
+    /// Attempts are counted before they are made, so that concurrent attempts cannot bypass the
+    /// limit. Successful attempts can be taken back with `forgive` or `reset`.
+    pub fn attempt(&self, key: K) -> eyre::Result<()> {
+        self.attempt_at(key, Instant::now())
+    }
+
+    fn attempt_at(&self, key: K, now: Instant) -> eyre::Result<()> {
+        let mut attempts = self.attempts.lock().unwrap();
+        if attempts.len() > PRUNE_THRESHOLD {
+            attempts.retain(|_key, attempts| now - attempts.last_attempt < FORGET_AFTER);
+        }
+        let attempts = attempts.entry(key).or_insert(Attempts {
+            count: 0,
+            last_attempt: now,
+        });
+        if now - attempts.last_attempt >= FORGET_AFTER {
+            attempts.count = 0;
+        }
+        if let Some(excess) = attempts.count.checked_sub(self.free_attempts) {
+            let delay = BASE_DELAY
+                .saturating_mul(2u32.saturating_pow(excess))
+                .min(MAX_DELAY);
+            let wait = (attempts.last_attempt + delay).saturating_duration_since(now);
+            if !wait.is_zero() {
+                bail!(
+                    "too many attempts, try again in {} seconds",
+                    wait.as_secs() + 1
+                );
+            }
+        }
+        attempts.count += 1;
+        attempts.last_attempt = now;
+        Ok(())
+    }
+
+    /// Takes back one attempt of the key, e.g. because it succeeded.
+    pub fn forgive(&self, key: &K) {
+        if let Some(attempts) = self.attempts.lock().unwrap().get_mut(key) {
+            attempts.count = attempts.count.saturating_sub(1);
+        }
+    }
+
+    /// Forgets all attempts of the key.
+    pub fn reset(&self, key: &K) {
+        self.attempts.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "key";
+
+    #[test]
+    fn free_attempts_need_no_delay() {
+        let limiter = RateLimiter::new(3);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.attempt_at(KEY, now).is_ok());
+        }
+        assert!(limiter.attempt_at(KEY, now).is_err());
+    }
+
+    #[test]
+    fn delay_doubles_with_each_further_attempt() {
+        let limiter = RateLimiter::new(1);
+        let mut now = Instant::now();
+        limiter.attempt_at(KEY, now).unwrap();
+        for delay in [1, 2, 4, 8] {
+            let delay = Duration::from_secs(delay);
+            assert!(limiter
+                .attempt_at(KEY, now + delay - Duration::from_millis(1))
+                .is_err());
+            now += delay;
+            assert!(limiter.attempt_at(KEY, now).is_ok());
+        }
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        let limiter = RateLimiter::new(1);
+        let mut now = Instant::now();
+        limiter.attempt_at(KEY, now).unwrap();
+        for _ in 0..40 {
+            now += MAX_DELAY;
+            assert!(limiter.attempt_at(KEY, now).is_ok());
+        }
+        assert!(limiter
+            .attempt_at(KEY, now + MAX_DELAY - Duration::from_millis(1))
+            .is_err());
+        assert!(limiter.attempt_at(KEY, now + MAX_DELAY).is_ok());
+    }
+
+    #[test]
+    fn attempts_are_forgotten_after_a_while() {
+        let limiter = RateLimiter::new(1);
+        let now = Instant::now();
+        limiter.attempt_at(KEY, now).unwrap();
+        limiter.attempt_at(KEY, now + BASE_DELAY).unwrap();
+        assert!(limiter.attempt_at(KEY, now + BASE_DELAY).is_err());
+        assert!(limiter
+            .attempt_at(KEY, now + BASE_DELAY + FORGET_AFTER)
+            .is_ok());
+    }
+
+    #[test]
+    fn forgiven_and_reset_attempts_do_not_count() {
+        let limiter = RateLimiter::new(1);
+        let now = Instant::now();
+        limiter.attempt_at(KEY, now).unwrap();
+        limiter.forgive(&KEY);
+        limiter.attempt_at(KEY, now).unwrap();
+        limiter.reset(&KEY);
+        assert!(limiter.attempt_at(KEY, now).is_ok());
+        assert!(limiter.attempt_at(KEY, now).is_err());
+    }
+}
@@ -1,30 +1,130 @@
 use argon2::password_hash::SaltString;
+use clap::ValueEnum;
 use eyre::{bail, Context as _, ContextCompat as _, Result};
 use rand_core::OsRng;
 use std::{
-    collections::BTreeSet,
-    ops::DerefMut as _,
+    collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
     sync::Arc,
     time::{Duration, SystemTime},
 };
 
-use tokio::sync::{broadcast, RwLock};
+use tokio::{
+    sync::{broadcast, RwLock},
+    task::spawn_blocking,
+};
+use tracing::{error, info};
 
 use crate::{
-    messages::Update,
-    storage::{AttendanceMode, Role, Storage, Talk, UserId},
+    client::Heartbeat,
+    conflicts::{conflicts_of, find_conflicts, Conflict, ConflictPolicy},
+    messages::{Invite, Session, Update},
+    rate_limit::RateLimits,
+    solver::{propose_schedule, ScheduleProposal},
+    storage::{
+        generate_session_id, hash_password, hash_token, verify_password, AttendanceMode, Draft,
+        DraftName, InviteData, InviteId, Location, PasswordReset, Role, Storage, Talk,
+        TalkSchedule, Token, TokenData, UserId,
+    },
+    storage_backend::StorageBackend as _,
+    updates::UpdatesSender,
 };
 
 #[derive(Debug, Clone)]
 pub struct Service {
     pub storage: Arc<RwLock<Storage>>,
-    pub updates_sender: broadcast::Sender<Update>,
+    pub updates_sender: UpdatesSender,
+    pub heartbeat: Heartbeat,
+    pub revocations_sender: broadcast::Sender<Revocation>,
+    pub token_lifetime: TokenLifetime,
+    pub rate_limits: Arc<RateLimits>,
+    pub registration_mode: RegistrationMode,
+}
+
+/// Who may register an account.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum RegistrationMode {
+    /// Anyone can register into any team.
+    Open,
+    /// Registering requires an invite code of the team, generated by an admin.
+    Invite,
+    /// Only admins can create accounts.
+    Closed,
+}
+
+/// Sessions that were revoked, connections authenticated with them are closed.
+#[derive(Clone, Debug)]
+pub enum Revocation {
+    Session(String),
+    User(UserId),
+}
+
+/// How long tokens are valid.
+#[derive(Clone, Copy, Debug)]
+pub struct TokenLifetime {
+    /// Validity of a token after it was issued, renewed with each relogin.
+    pub lifetime: Duration,
+    /// Age after which a session ends even if it was renewed, requiring a new login.
+    pub max_session_age: Duration,
+}
+
+impl TokenLifetime {
+    fn expires_at(&self, created_at: SystemTime, now: SystemTime) -> SystemTime {
+        (now + self.lifetime).min(created_at + self.max_session_age)
+    }
 }
 
 pub struct Authentication {
     pub user_id: UserId,
     pub roles: BTreeSet<Role>,
     pub token: String,
+    pub session_id: String,
+}
+
+/// The device a client connects from, recorded for each session.
+#[derive(Clone, Debug)]
+pub struct Peer {
+    pub user_agent: Option<String>,
+    pub address: SocketAddr,
+}
+
+async fn hash_password_blocking(password: String) -> Result<String> {
+    spawn_blocking(move || hash_password(&password))
+        .await
+        .wrap_err("failed to join password hashing task")
+}
+
+async fn verify_password_blocking(hash: String, password: String) -> Result<bool> {
+    spawn_blocking(move || verify_password(&hash, &password))
+        .await
+        .wrap_err("failed to join password verification task")
+}
+
+const PASSWORD_RESET_LIFETIME: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// How long a token stays valid after a relogin replaced it, in case the client never got the
+/// new one, e.g. because the connection dropped right after the relogin.
+const RELOGIN_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+fn generate_token() -> Token {
+    SaltString::generate(&mut OsRng).to_string()
+}
+
+fn new_token_data(
+    user_id: UserId,
+    peer: &Peer,
+    now: SystemTime,
+    token_lifetime: TokenLifetime,
+) -> TokenData {
+    TokenData {
+        user_id,
+        expires_at: token_lifetime.expires_at(now, now),
+        session_id: generate_session_id(),
+        created_at: now,
+        last_used_at: now,
+        user_agent: peer.user_agent.clone(),
+        remote_address: Some(peer.address),
+    }
 }
 
 impl Service {
@@ -34,9 +134,72 @@ impl Service {
         team: String,
         attendance_mode: AttendanceMode,
         password: String,
+        invite_code: Option<String>,
+        peer: &Peer,
     ) -> eyre::Result<Authentication> {
+        if self.registration_mode == RegistrationMode::Closed {
+            bail!("registration is closed, ask an admin to create an account");
+        }
+        let address = peer.address.ip();
+        self.rate_limits.registrations.attempt(address)?;
+        if self.registration_mode == RegistrationMode::Invite {
+            self.rate_limits.invite_codes.attempt(address)?;
+        }
+        let hash = hash_password_blocking(password).await?;
+
+        let storage = &mut self.storage.write().await;
+
+        if self.registration_mode == RegistrationMode::Invite {
+            let code_hash = hash_token(
+                &invite_code.wrap_err("registration requires an invite code of the team")?,
+            );
+            if !storage
+                .invites
+                .values()
+                .any(|invite| invite.code_hash == code_hash && invite.team == team)
+            {
+                bail!("invalid invite code for team {team}");
+            }
+            self.rate_limits.invite_codes.forgive(&address);
+        }
+
+        let new_user_id = self
+            .add_user(storage, name, team, attendance_mode, hash)
+            .await?;
+        self.start_session(storage, new_user_id, BTreeSet::new(), peer)
+            .await
+    }
+
+    /// Creates an account for someone else, only allowed for admins.
+    pub async fn create_user(
+        &self,
+        user_id: UserId,
+        name: String,
+        team: String,
+        attendance_mode: AttendanceMode,
+        password: String,
+    ) -> Result<UserId> {
+        let hash = hash_password_blocking(password).await?;
         let storage = &mut self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_admin() {
+            bail!("only admins can create users");
+        }
+        let new_user_id = self
+            .add_user(storage, name, team, attendance_mode, hash)
+            .await?;
+        info!("User {user_id} created user {new_user_id}");
+        Ok(new_user_id)
+    }
 
+    async fn add_user(
+        &self,
+        storage: &mut Storage,
+        name: String,
+        team: String,
+        attendance_mode: AttendanceMode,
+        hash: String,
+    ) -> Result<UserId> {
         if !storage.teams.contains(&team) {
             bail!("unknown team {team}");
         }
@@ -49,12 +212,11 @@ impl Service {
             bail!("user {name} from team {team} already exists");
         }
 
-        let new_user_id = storage.add_user(name, team, attendance_mode, password);
+        let new_user_id = storage.add_user(name, team, attendance_mode, hash);
         storage
-            .users
-            .commit()
+            .store_user(new_user_id)
             .await
-            .expect("failed to commit users");
+            .wrap_err("failed to commit users")?;
 
         // Inform all connected clients about the new user.
         let users_update = Update::Users {
@@ -65,24 +227,7 @@ impl Service {
                 .collect(),
         };
         let _ = self.updates_sender.send(users_update);
-
-        let token = SaltString::generate(&mut OsRng).to_string();
-        let now = SystemTime::now();
-        let seven_days = Duration::from_secs(60 * 60 * 24 * 7);
-        storage.tokens.remove_expired(now);
-        storage
-            .tokens
-            .insert(token.clone(), new_user_id, now + seven_days);
-        storage
-            .tokens
-            .commit()
-            .await
-            .wrap_err("failed to commit tokens")?;
-        Ok(Authentication {
-            user_id: new_user_id,
-            roles: BTreeSet::new(),
-            token,
-        })
+        Ok(new_user_id)
     }
 
     pub async fn login(
@@ -90,47 +235,147 @@ impl Service {
         name: String,
         team: String,
         password: String,
+        peer: &Peer,
     ) -> eyre::Result<Authentication> {
-        let storage = &mut self.storage.write().await;
-        let Storage { users, tokens, .. } = storage.deref_mut();
+        let address = peer.address.ip();
+        let account = (name.clone(), team.clone());
+        self.rate_limits.addresses.attempt(address)?;
+        self.rate_limits.accounts.attempt(account.clone())?;
+
+        let hash = self
+            .storage
+            .read()
+            .await
+            .users
+            .values()
+            .find(|user| user.name == name && user.team == team)
+            .map(|user| user.hash.clone())
+            .wrap_err_with(|| format!("unknown user {name} from team {team}"))?;
+        // Verify without holding the lock, since it takes a while
+        if !verify_password_blocking(hash, password).await? {
+            bail!("wrong password");
+        }
+        self.rate_limits.addresses.forgive(&address);
+        self.rate_limits.accounts.reset(&account);
 
-        let Some(user) = users
+        let storage = &mut self.storage.write().await;
+        let user = storage
+            .users
             .values()
             .find(|user| user.name == name && user.team == team)
+            .wrap_err_with(|| format!("unknown user {name} from team {team}"))?;
+        let (user_id, roles) = (user.id, user.roles.clone());
+        self.start_session(storage, user_id, roles, peer).await
+    }
+
+    /// Sets a new password with a reset token issued by an admin, then logs the user in.
+    pub async fn reset_password(
+        &self,
+        name: String,
+        team: String,
+        reset_token: String,
+        new_password: String,
+        peer: &Peer,
+    ) -> eyre::Result<Authentication> {
+        let address = peer.address.ip();
+        self.rate_limits.addresses.attempt(address)?;
+        let hash = hash_password_blocking(new_password).await?;
+
+        let storage = &mut self.storage.write().await;
+
+        let Some(user) = storage
+            .users
+            .values_mut()
+            .find(|user| user.name == name && user.team == team)
         else {
             bail!("unknown user {name} from team {team}");
         };
 
-        if !user.verify(password) {
-            bail!("wrong password");
+        let Some(password_reset) = &user.password_reset else {
+            bail!("no password reset was issued");
+        };
+        if password_reset.expires_at < SystemTime::now()
+            || password_reset.token_hash != hash_token(&reset_token)
+        {
+            bail!("invalid or expired reset token");
         }
+        user.password_reset = None;
+        user.hash = hash;
+        self.rate_limits.addresses.forgive(&address);
+        self.rate_limits.accounts.reset(&(name, team));
 
-        let token = SaltString::generate(&mut OsRng).to_string();
+        let (user_id, roles) = (user.id, user.roles.clone());
+        storage
+            .store_user(user_id)
+            .await
+            .wrap_err("failed to commit users")?;
+        info!("User {user_id} reset their password");
+        self.remove_other_sessions(storage, user_id, None).await?;
+        self.start_session(storage, user_id, roles, peer).await
+    }
+
+    async fn start_session(
+        &self,
+        storage: &mut Storage,
+        user_id: UserId,
+        roles: BTreeSet<Role>,
+        peer: &Peer,
+    ) -> eyre::Result<Authentication> {
+        let token = generate_token();
         let now = SystemTime::now();
-        let seven_days = Duration::from_secs(60 * 60 * 24 * 7);
-        tokens.remove_expired(now);
-        tokens.insert(token.clone(), user.id, now + seven_days);
-        tokens.commit().await.wrap_err("failed to commit tokens")?;
+        let token_data = new_token_data(user_id, peer, now, self.token_lifetime);
+        let session_id = token_data.session_id.clone();
+        storage.tokens.remove_expired(now);
+        storage.tokens.insert(&token, token_data);
+        storage
+            .backend
+            .store_tokens(&storage.tokens)
+            .await
+            .wrap_err("failed to commit tokens")?;
 
         Ok(Authentication {
-            user_id: user.id,
-            roles: user.roles.clone(),
+            user_id,
+            roles,
             token,
+            session_id,
         })
     }
 
-    pub async fn relogin(&self, token: String) -> eyre::Result<Authentication> {
+    /// Renews the session of the token and returns a new token replacing it.
+    ///
+    /// The replaced token stays valid for `RELOGIN_GRACE_PERIOD`, so a client that loses the new
+    /// token to a dropped connection is not logged out.
+    pub async fn relogin(&self, token: String, peer: &Peer) -> eyre::Result<Authentication> {
         let storage = &mut self.storage.write().await;
 
         let now = SystemTime::now();
         storage.tokens.remove_expired(now);
-        storage
+        let renewed = storage
             .tokens
-            .commit()
+            .expire_at(&token, now + RELOGIN_GRACE_PERIOD)
+            .map(|data| {
+                let new_token = generate_token();
+                let data = TokenData {
+                    expires_at: self.token_lifetime.expires_at(data.created_at, now),
+                    last_used_at: now,
+                    user_agent: peer.user_agent.clone(),
+                    remote_address: Some(peer.address),
+                    ..data
+                };
+                (new_token, data)
+            });
+        // Sessions reaching their maximum age have expired right now
+        let renewed = renewed.filter(|(_new_token, data)| data.expires_at > now);
+        if let Some((new_token, data)) = &renewed {
+            storage.tokens.insert(new_token, data.clone());
+        }
+        storage
+            .backend
+            .store_tokens(&storage.tokens)
             .await
             .wrap_err("failed to commit tokens")?;
 
-        let data = storage.tokens.get(&token).wrap_err("unknown token")?;
+        let (new_token, data) = renewed.wrap_err("unknown token")?;
         let user = storage
             .users
             .get(&data.user_id)
@@ -139,7 +384,8 @@ impl Service {
         Ok(Authentication {
             user_id: user.id,
             roles: user.roles.clone(),
-            token,
+            token: new_token,
+            session_id: data.session_id,
         })
     }
 
@@ -150,8 +396,8 @@ impl Service {
         description: String,
         duration: Duration,
     ) -> Result<()> {
-        let talks = &mut self.storage.write().await.talks;
-        let max_talk_id = talks.keys().copied().max().unwrap_or_default();
+        let mut storage = self.storage.write().await;
+        let max_talk_id = storage.talks.keys().copied().max().unwrap_or_default();
         let next_talk_id = max_talk_id + 1;
         let talk = Talk {
             id: next_talk_id,
@@ -165,48 +411,58 @@ impl Service {
             nerds: BTreeSet::from([user_id]),
             noobs: Default::default(),
         };
-        talks.insert(next_talk_id, talk.clone());
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self.updates_sender.send(Update::AddTalk { talk });
+        let update = Update::AddTalk { talk };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit talks")?;
+        let _ = self.updates_sender.send(update);
         Ok(())
     }
 
     pub async fn remove_talk(&self, talk_id: usize, user_id: UserId) -> Result<()> {
         let mut storage = self.storage.write().await;
-        let Storage { users, talks, .. } = storage.deref_mut();
-        let user = users
+        let user = storage
+            .users
             .get(&user_id)
             .wrap_err_with(|| format!("user {user_id} does not exist"))?;
-        let talk = talks
-            .get_mut(&talk_id)
+        let talk = storage
+            .talks
+            .get(&talk_id)
             .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
         if !(user.is_editor() || user.is_scheduler() || user.is_creator(talk)) {
             bail!("user cannot edit talk with id {talk_id}");
         }
-        let id = talk.id;
-        talks.remove(&id);
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self.updates_sender.send(Update::RemoveTalk { talk_id });
+        let conflicts_before = find_conflicts(&storage.talks);
+        let update = Update::RemoveTalk { talk_id };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit talks")?;
+        let _ = self.updates_sender.send(update);
+        self.send_conflicts(&storage, &conflicts_before);
         Ok(())
     }
 
     pub async fn update_title(&self, talk_id: usize, user_id: UserId, title: String) -> Result<()> {
         let mut storage = self.storage.write().await;
-        let Storage { users, talks, .. } = storage.deref_mut();
-        let user = users
+        let user = storage
+            .users
             .get(&user_id)
             .wrap_err_with(|| format!("user {user_id} does not exist"))?;
-        let talk = talks
-            .get_mut(&talk_id)
+        let talk = storage
+            .talks
+            .get(&talk_id)
             .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
         if !(user.is_editor() || user.is_creator(talk)) {
             bail!("user cannot edit talk with id {talk_id}");
         }
-        talk.title = title.clone();
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self
-            .updates_sender
-            .send(Update::UpdateTitle { talk_id, title });
+        let update = Update::UpdateTitle { talk_id, title };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit talks")?;
+        let _ = self.updates_sender.send(update);
         Ok(())
     }
 
@@ -217,22 +473,26 @@ impl Service {
         description: String,
     ) -> Result<()> {
         let mut storage = self.storage.write().await;
-        let Storage { users, talks, .. } = storage.deref_mut();
-        let user = users
+        let user = storage
+            .users
             .get(&user_id)
             .wrap_err_with(|| format!("user {user_id} does not exist"))?;
-        let talk = talks
-            .get_mut(&talk_id)
+        let talk = storage
+            .talks
+            .get(&talk_id)
             .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
         if !(user.is_editor() || user.is_creator(talk)) {
             bail!("user cannot edit talk with id {talk_id}");
         }
-        talk.description = description.clone();
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self.updates_sender.send(Update::UpdateDescription {
+        let update = Update::UpdateDescription {
             talk_id,
             description,
-        });
+        };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit talks")?;
+        let _ = self.updates_sender.send(update);
         Ok(())
     }
 
@@ -241,27 +501,36 @@ impl Service {
         talk_id: usize,
         user_id: UserId,
         scheduled_at: Option<SystemTime>,
-    ) -> Result<()> {
+    ) -> Result<Option<String>> {
         let mut storage = self.storage.write().await;
-        let Storage { users, talks, .. } = storage.deref_mut();
-        let user = users
+        let user = storage
+            .users
             .get(&user_id)
             .wrap_err_with(|| format!("user {user_id} does not exist"))?;
-        let talk = {
-            talks
-                .get_mut(&talk_id)
-                .wrap_err_with(|| format!("talk {talk_id} does not exist"))
-        }?;
+        let talk = storage
+            .talks
+            .get(&talk_id)
+            .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
         if !user.is_scheduler() {
             bail!("user cannot schedule talks");
         }
-        talk.scheduled_at = scheduled_at;
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self.updates_sender.send(Update::UpdateScheduledAt {
+        let candidate = Talk {
+            scheduled_at,
+            ..talk.clone()
+        };
+        storage.event.check_schedule(&candidate)?;
+        let update = Update::UpdateScheduledAt {
             talk_id,
             scheduled_at,
-        });
-        Ok(())
+        };
+        self.commit_talk_change(
+            &mut storage,
+            user_id,
+            candidate,
+            update,
+            "refusing to schedule conflicting talks",
+        )
+        .await
     }
 
     pub async fn update_duration(
@@ -269,24 +538,33 @@ impl Service {
         talk_id: usize,
         user_id: UserId,
         duration: Duration,
-    ) -> Result<()> {
+    ) -> Result<Option<String>> {
         let mut storage = self.storage.write().await;
-        let Storage { users, talks, .. } = storage.deref_mut();
-        let user = users
+        let user = storage
+            .users
             .get(&user_id)
             .wrap_err_with(|| format!("user {user_id} does not exist"))?;
-        let talk = talks
-            .get_mut(&talk_id)
+        let talk = storage
+            .talks
+            .get(&talk_id)
             .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
         if !(user.is_scheduler() || user.is_creator(talk)) {
             bail!("user cannot change duration of talk with id {talk_id}");
         }
-        talk.duration = duration;
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self
-            .updates_sender
-            .send(Update::UpdateDuration { talk_id, duration });
-        Ok(())
+        let candidate = Talk {
+            duration,
+            ..talk.clone()
+        };
+        storage.event.check_schedule(&candidate)?;
+        let update = Update::UpdateDuration { talk_id, duration };
+        self.commit_talk_change(
+            &mut storage,
+            user_id,
+            candidate,
+            update,
+            "refusing to schedule conflicting talks",
+        )
+        .await
     }
 
     pub async fn update_location(
@@ -294,75 +572,138 @@ impl Service {
         talk_id: usize,
         user_id: UserId,
         location: Option<usize>,
-    ) -> Result<()> {
+    ) -> Result<Option<String>> {
         let mut storage = self.storage.write().await;
-        let Storage { users, talks, .. } = storage.deref_mut();
-        let user = users
+        let user = storage
+            .users
             .get(&user_id)
             .wrap_err_with(|| format!("user {user_id} does not exist"))?;
-        let talk = talks
-            .get_mut(&talk_id)
+        let talk = storage
+            .talks
+            .get(&talk_id)
             .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
         if !(user.is_scheduler() || user.is_creator(talk)) {
             bail!("user cannot schedule talks");
         }
-        talk.location = location;
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self
-            .updates_sender
-            .send(Update::UpdateLocation { talk_id, location });
-        Ok(())
+        if let Some(location_id) = location {
+            if !storage.locations.contains_key(&location_id) {
+                bail!("location {location_id} does not exist");
+            }
+        }
+        let candidate = Talk {
+            location,
+            ..talk.clone()
+        };
+        let update = Update::UpdateLocation { talk_id, location };
+        self.commit_talk_change(
+            &mut storage,
+            user_id,
+            candidate,
+            update,
+            "refusing to schedule conflicting talks",
+        )
+        .await
+    }
+
+    /// Commits a change of a talk that may cause conflicts, resulting in `candidate`.
+    ///
+    /// Conflicts introduced by the change are refused with the `refusal` message or returned as
+    /// warning, depending on the policy for their kind.
+    async fn commit_talk_change(
+        &self,
+        storage: &mut Storage,
+        user_id: UserId,
+        candidate: Talk,
+        update: Update,
+        refusal: &str,
+    ) -> Result<Option<String>> {
+        let conflicts_before = find_conflicts(&storage.talks);
+        let new_conflicts: Vec<_> = conflicts_of(&candidate, &storage.talks)
+            .difference(&conflicts_before)
+            .cloned()
+            .collect();
+        self.commit_with_conflicts(
+            storage,
+            user_id,
+            &conflicts_before,
+            new_conflicts,
+            update,
+            refusal,
+        )
+        .await
+    }
+
+    /// Commits a change introducing `new_conflicts`, refusing it or returning them as warning.
+    async fn commit_with_conflicts(
+        &self,
+        storage: &mut Storage,
+        user_id: UserId,
+        conflicts_before: &BTreeSet<Conflict>,
+        new_conflicts: Vec<Conflict>,
+        update: Update,
+        refusal: &str,
+    ) -> Result<Option<String>> {
+        let warning = check_conflicts(storage, &new_conflicts, refusal)?;
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit talks")?;
+        let _ = self.updates_sender.send(update);
+        self.send_conflicts(storage, conflicts_before);
+        Ok(warning)
+    }
+
+    /// Sends all conflicts if they changed since `conflicts_before` was taken.
+    fn send_conflicts(&self, storage: &Storage, conflicts_before: &BTreeSet<Conflict>) {
+        let conflicts = find_conflicts(&storage.talks);
+        if &conflicts != conflicts_before {
+            let _ = self.updates_sender.send(Update::Conflicts { conflicts });
+        }
     }
 
     pub async fn add_noob(&self, talk_id: usize, user_id: usize) -> Result<(), eyre::Error> {
-        let talks = &mut self.storage.write().await.talks;
-        let talk = talks
-            .get_mut(&talk_id)
-            .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
-        talk.noobs.insert(user_id);
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self
-            .updates_sender
-            .send(Update::AddNoob { talk_id, user_id });
-        Ok(())
+        self.commit_participation(user_id, Update::AddNoob { talk_id, user_id })
+            .await
     }
 
     pub async fn remove_noob(&self, talk_id: usize, user_id: usize) -> Result<(), eyre::Error> {
-        let talks = &mut self.storage.write().await.talks;
-        let talk = talks
-            .get_mut(&talk_id)
-            .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
-        talk.noobs.remove(&user_id);
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self
-            .updates_sender
-            .send(Update::RemoveNoob { talk_id, user_id });
-        Ok(())
+        self.commit_participation(user_id, Update::RemoveNoob { talk_id, user_id })
+            .await
     }
 
-    pub async fn add_nerd(&self, talk_id: usize, user_id: usize) -> Result<(), eyre::Error> {
-        let talks = &mut self.storage.write().await.talks;
-        let talk = talks
-            .get_mut(&talk_id)
+    pub async fn add_nerd(&self, talk_id: usize, user_id: usize) -> Result<Option<String>> {
+        let mut storage = self.storage.write().await;
+        let talk = storage
+            .talks
+            .get(&talk_id)
             .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
-        talk.nerds.insert(user_id);
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self
-            .updates_sender
-            .send(Update::AddNerd { talk_id, user_id });
-        Ok(())
+        let mut candidate = talk.clone();
+        candidate.nerds.insert(user_id);
+        let update = Update::AddNerd { talk_id, user_id };
+        self.commit_talk_change(
+            &mut storage,
+            user_id,
+            candidate,
+            update,
+            &format!("cannot join talk {talk_id} as a nerd"),
+        )
+        .await
     }
 
     pub async fn remove_nerd(&self, talk_id: usize, user_id: usize) -> Result<(), eyre::Error> {
-        let talks = &mut self.storage.write().await.talks;
-        let talk = talks
-            .get_mut(&talk_id)
-            .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
-        talk.nerds.remove(&user_id);
-        talks.commit().await.wrap_err("failed to commit talks")?;
-        let _ = self
-            .updates_sender
-            .send(Update::RemoveNerd { talk_id, user_id });
+        self.commit_participation(user_id, Update::RemoveNerd { talk_id, user_id })
+            .await
+    }
+
+    async fn commit_participation(&self, user_id: usize, update: Update) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        let conflicts_before = find_conflicts(&storage.talks);
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit talks")?;
+        let _ = self.updates_sender.send(update);
+        self.send_conflicts(&storage, &conflicts_before);
         Ok(())
     }
 
@@ -372,16 +713,762 @@ impl Service {
         attendance_mode: crate::storage::AttendanceMode,
     ) -> Result<()> {
         let mut storage = self.storage.write().await;
-        let Storage { users, .. } = storage.deref_mut();
-        let user = users
-            .get_mut(&user_id)
-            .wrap_err_with(|| format!("user {user_id} does not exist"))?;
-        user.attendance_mode = attendance_mode;
-        users.commit().await.wrap_err("failed to commit users")?;
-        let _ = self.updates_sender.send(Update::UpdateAttendanceMode {
+        if !storage.users.contains_key(&user_id) {
+            bail!("user {user_id} does not exist");
+        }
+        let update = Update::UpdateAttendanceMode {
             user_id,
             attendance_mode,
+        };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit users")?;
+        let _ = self.updates_sender.send(update);
+        Ok(())
+    }
+
+    pub async fn logout_all(&self, user_id: UserId) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        self.remove_tokens_of(&mut storage, user_id).await
+    }
+
+    pub async fn revoke_tokens(&self, user_id: UserId, target_user_id: UserId) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_admin() {
+            bail!("only admins can revoke tokens of other users");
+        }
+        if !storage.users.contains_key(&target_user_id) {
+            bail!("user {target_user_id} does not exist");
+        }
+        self.remove_tokens_of(&mut storage, target_user_id).await
+    }
+
+    pub async fn grant_role(
+        &self,
+        user_id: UserId,
+        target_user_id: UserId,
+        role: Role,
+    ) -> Result<()> {
+        self.change_roles(user_id, target_user_id, |roles| {
+            roles.insert(role);
+        })
+        .await
+    }
+
+    pub async fn revoke_role(
+        &self,
+        user_id: UserId,
+        target_user_id: UserId,
+        role: Role,
+    ) -> Result<()> {
+        self.change_roles(user_id, target_user_id, |roles| {
+            roles.remove(&role);
+        })
+        .await
+    }
+
+    async fn change_roles(
+        &self,
+        user_id: UserId,
+        target_user_id: UserId,
+        change: impl FnOnce(&mut BTreeSet<Role>),
+    ) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_admin() {
+            bail!("only admins can change roles");
+        }
+        let target_user = storage
+            .users
+            .get(&target_user_id)
+            .wrap_err_with(|| format!("user {target_user_id} does not exist"))?;
+        let mut roles = target_user.roles.clone();
+        change(&mut roles);
+        if roles == target_user.roles {
+            return Ok(());
+        }
+        if target_user.is_admin()
+            && !roles.contains(&Role::Admin)
+            && storage
+                .users
+                .values()
+                .filter(|user| user.is_admin())
+                .count()
+                == 1
+        {
+            bail!("cannot revoke the role of the last admin");
+        }
+        info!("User {user_id} changed roles of user {target_user_id} to {roles:?}");
+        let update = Update::UpdateRoles {
+            user_id: target_user_id,
+            roles,
+        };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit users")?;
+        let _ = self.updates_sender.send(update);
+        Ok(())
+    }
+
+    /// Active sessions of the user, the one with `session_id` is marked as current.
+    pub async fn list_sessions(&self, user_id: UserId, session_id: &str) -> Vec<Session> {
+        let storage = self.storage.read().await;
+        let now = SystemTime::now();
+        let mut newest_tokens: BTreeMap<&str, &TokenData> = BTreeMap::new();
+        for (_token, data) in storage.tokens.iter() {
+            if data.user_id != user_id || data.expires_at < now {
+                continue;
+            }
+            // Tokens replaced on relogin belong to the same session until they expire
+            let newest = newest_tokens.entry(&data.session_id).or_insert(data);
+            if data.expires_at > newest.expires_at {
+                *newest = data;
+            }
+        }
+        newest_tokens
+            .into_values()
+            .map(|data| Session {
+                session_id: data.session_id.clone(),
+                created_at: data.created_at,
+                last_used_at: data.last_used_at,
+                expires_at: data.expires_at,
+                user_agent: data.user_agent.clone(),
+                remote_address: data.remote_address,
+                current: data.session_id == session_id,
+            })
+            .collect()
+    }
+
+    pub async fn revoke_session(&self, user_id: UserId, session_id: String) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        if storage
+            .tokens
+            .find_session(&session_id)
+            .is_none_or(|data| data.user_id != user_id)
+        {
+            bail!("unknown session {session_id}");
+        }
+        storage.tokens.remove_session(&session_id);
+        storage
+            .backend
+            .store_tokens(&storage.tokens)
+            .await
+            .wrap_err("failed to commit tokens")?;
+        let _ = self
+            .revocations_sender
+            .send(Revocation::Session(session_id));
+        Ok(())
+    }
+
+    async fn remove_tokens_of(&self, storage: &mut Storage, user_id: UserId) -> Result<()> {
+        let count = storage.tokens.remove_user(user_id);
+        storage
+            .backend
+            .store_tokens(&storage.tokens)
+            .await
+            .wrap_err("failed to commit tokens")?;
+        info!("Revoked {count} tokens of user {user_id}");
+        let _ = self.revocations_sender.send(Revocation::User(user_id));
+        Ok(())
+    }
+
+    /// Ends all sessions of the user except the one with `kept_session_id`.
+    async fn remove_other_sessions(
+        &self,
+        storage: &mut Storage,
+        user_id: UserId,
+        kept_session_id: Option<&str>,
+    ) -> Result<()> {
+        let session_ids: BTreeSet<String> = storage
+            .tokens
+            .iter()
+            .filter(|(_token, data)| {
+                data.user_id == user_id && Some(data.session_id.as_str()) != kept_session_id
+            })
+            .map(|(_token, data)| data.session_id.clone())
+            .collect();
+        for session_id in &session_ids {
+            storage.tokens.remove_session(session_id);
+        }
+        storage
+            .backend
+            .store_tokens(&storage.tokens)
+            .await
+            .wrap_err("failed to commit tokens")?;
+        info!(
+            "Revoked {} other sessions of user {user_id}",
+            session_ids.len()
+        );
+        for session_id in session_ids {
+            let _ = self
+                .revocations_sender
+                .send(Revocation::Session(session_id));
+        }
+        Ok(())
+    }
+
+    /// Sets a new password and ends all other sessions of the user.
+    pub async fn change_password(
+        &self,
+        user_id: UserId,
+        session_id: &str,
+        old_password: String,
+        new_password: String,
+    ) -> Result<()> {
+        self.rate_limits.password_changes.attempt(user_id)?;
+        let hash = self
+            .storage
+            .read()
+            .await
+            .users
+            .get(&user_id)
+            .wrap_err("unknown user")?
+            .hash
+            .clone();
+        if !verify_password_blocking(hash, old_password).await? {
+            bail!("wrong password");
+        }
+        self.rate_limits.password_changes.reset(&user_id);
+        let hash = hash_password_blocking(new_password).await?;
+
+        let mut storage = self.storage.write().await;
+        storage
+            .users
+            .get_mut(&user_id)
+            .wrap_err("unknown user")?
+            .hash = hash;
+        storage
+            .store_user(user_id)
+            .await
+            .wrap_err("failed to commit users")?;
+        self.remove_other_sessions(&mut storage, user_id, Some(session_id))
+            .await
+    }
+
+    /// Issues a single-use token for the user to set a new password, only allowed for admins.
+    pub async fn issue_password_reset(
+        &self,
+        user_id: UserId,
+        target_user_id: UserId,
+    ) -> Result<(String, SystemTime)> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_admin() {
+            bail!("only admins can issue password resets");
+        }
+        let target_user = storage
+            .users
+            .get_mut(&target_user_id)
+            .wrap_err_with(|| format!("user {target_user_id} does not exist"))?;
+        let reset_token = generate_token();
+        let expires_at = SystemTime::now() + PASSWORD_RESET_LIFETIME;
+        target_user.password_reset = Some(PasswordReset {
+            token_hash: hash_token(&reset_token),
+            expires_at,
+        });
+        storage
+            .store_user(target_user_id)
+            .await
+            .wrap_err("failed to commit users")?;
+        info!("User {user_id} issued a password reset for user {target_user_id}");
+        Ok((reset_token, expires_at))
+    }
+
+    /// Generates an invite code for registering into the team, only allowed for admins.
+    pub async fn create_invite(&self, user_id: UserId, team: String) -> Result<(InviteId, String)> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_admin() {
+            bail!("only admins can create invites");
+        }
+        if !storage.teams.contains(&team) {
+            bail!("unknown team {team}");
+        }
+        let invite_id = generate_session_id();
+        let invite_code = generate_token();
+        storage.invites.insert(
+            invite_id.clone(),
+            InviteData {
+                team,
+                code_hash: hash_token(&invite_code),
+                created_by: user_id,
+                created_at: SystemTime::now(),
+            },
+        );
+        storage
+            .backend
+            .store_invites(&storage.invites)
+            .await
+            .wrap_err("failed to commit invites")?;
+        info!("User {user_id} created invite {invite_id}");
+        Ok((invite_id, invite_code))
+    }
+
+    pub async fn list_invites(&self, user_id: UserId) -> Result<Vec<Invite>> {
+        let storage = self.storage.read().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_admin() {
+            bail!("only admins can list invites");
+        }
+        Ok(storage
+            .invites
+            .iter()
+            .map(|(invite_id, invite)| Invite {
+                invite_id: invite_id.clone(),
+                team: invite.team.clone(),
+                created_by: invite.created_by,
+                created_at: invite.created_at,
+            })
+            .collect())
+    }
+
+    pub async fn revoke_invite(&self, user_id: UserId, invite_id: InviteId) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_admin() {
+            bail!("only admins can revoke invites");
+        }
+        if storage.invites.remove(&invite_id).is_none() {
+            bail!("unknown invite {invite_id}");
+        }
+        storage
+            .backend
+            .store_invites(&storage.invites)
+            .await
+            .wrap_err("failed to commit invites")?;
+        info!("User {user_id} revoked invite {invite_id}");
+        Ok(())
+    }
+
+    pub async fn create_team(&self, user_id: UserId, team: String) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_admin() {
+            bail!("only admins can manage teams");
+        }
+        let team = team.trim().to_string();
+        if team.is_empty() {
+            bail!("team name must not be empty");
+        }
+        if storage.teams.contains(&team) {
+            bail!("team {team} already exists");
+        }
+        let update = Update::AddTeam { team };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit teams")?;
+        let _ = self.updates_sender.send(update);
+        Ok(())
+    }
+
+    pub async fn rename_team(&self, user_id: UserId, team: String, new_name: String) -> Result<()> {
+        let new_name = new_name.trim().to_string();
+        if new_name.is_empty() {
+            bail!("team name must not be empty");
+        }
+        let mut storage = self.storage.write().await;
+        if storage.teams.contains(&new_name) {
+            bail!("team {new_name} already exists, merge the teams instead");
+        }
+        self.move_team(&mut storage, user_id, team, new_name).await
+    }
+
+    pub async fn merge_teams(&self, user_id: UserId, team: String, into: String) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        if !storage.teams.contains(&into) {
+            bail!("unknown team {into}");
+        }
+        if team == into {
+            bail!("cannot merge team {team} into itself");
+        }
+        if let Some(user) = storage.users.values().find(|user| {
+            user.team == team
+                && storage
+                    .users
+                    .values()
+                    .any(|other| other.team == into && other.name == user.name)
+        }) {
+            bail!("user {} exists in both teams", user.name);
+        }
+        self.move_team(&mut storage, user_id, team, into).await
+    }
+
+    /// Replaces the team by `new_name` for its users and invites.
+    async fn move_team(
+        &self,
+        storage: &mut Storage,
+        user_id: UserId,
+        team: String,
+        new_name: String,
+    ) -> Result<()> {
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_admin() {
+            bail!("only admins can manage teams");
+        }
+        if !storage.teams.contains(&team) {
+            bail!("unknown team {team}");
+        }
+        info!("User {user_id} moved team {team} to {new_name}");
+        let update = Update::RenameTeam {
+            team: team.clone(),
+            new_name: new_name.clone(),
+        };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit teams")?;
+        let _ = self.updates_sender.send(update);
+
+        let mut invites_changed = false;
+        for invite in storage.invites.values_mut() {
+            if invite.team == team {
+                invite.team = new_name.clone();
+                invites_changed = true;
+            }
+        }
+        if invites_changed {
+            storage
+                .backend
+                .store_invites(&storage.invites)
+                .await
+                .wrap_err("failed to commit invites")?;
+        }
+        Ok(())
+    }
+
+    pub async fn delete_team(&self, user_id: UserId, team: String) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_admin() {
+            bail!("only admins can manage teams");
+        }
+        if !storage.teams.contains(&team) {
+            bail!("unknown team {team}");
+        }
+        let member_count = storage
+            .users
+            .values()
+            .filter(|user| user.team == team)
+            .count();
+        if member_count > 0 {
+            bail!("team {team} still has {member_count} users, merge it into another team instead");
+        }
+        let update = Update::RemoveTeam { team: team.clone() };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit teams")?;
+        let _ = self.updates_sender.send(update);
+
+        let invite_count = storage.invites.len();
+        storage
+            .invites
+            .retain(|_invite_id, invite| invite.team != team);
+        if storage.invites.len() != invite_count {
+            storage
+                .backend
+                .store_invites(&storage.invites)
+                .await
+                .wrap_err("failed to commit invites")?;
+        }
+        info!("User {user_id} deleted team {team}");
+        Ok(())
+    }
+
+    /// Adds the location with the next free id, ignoring the id it has.
+    pub async fn create_location(&self, user_id: UserId, mut location: Location) -> Result<()> {
+        validate_location(&location)?;
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_scheduler() {
+            bail!("only schedulers can manage locations");
+        }
+        location.id = storage
+            .locations
+            .keys()
+            .next_back()
+            .map_or(0, |location_id| location_id + 1);
+        let update = Update::AddLocation { location };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit locations")?;
+        let _ = self.updates_sender.send(update);
+        Ok(())
+    }
+
+    pub async fn edit_location(&self, user_id: UserId, location: Location) -> Result<()> {
+        validate_location(&location)?;
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_scheduler() {
+            bail!("only schedulers can manage locations");
+        }
+        if !storage.locations.contains_key(&location.id) {
+            bail!("location {} does not exist", location.id);
+        }
+        let update = Update::ChangeLocation { location };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit locations")?;
+        let _ = self.updates_sender.send(update);
+        Ok(())
+    }
+
+    pub async fn delete_location(&self, user_id: UserId, location_id: usize) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_scheduler() {
+            bail!("only schedulers can manage locations");
+        }
+        if !storage.locations.contains_key(&location_id) {
+            bail!("location {location_id} does not exist");
+        }
+        let conflicts_before = find_conflicts(&storage.talks);
+        let affected_talk_ids: Vec<_> = storage
+            .talks
+            .values()
+            .filter(|talk| talk.location == Some(location_id))
+            .map(|talk| talk.id)
+            .collect();
+        for talk_id in affected_talk_ids {
+            let update = Update::UpdateLocation {
+                talk_id,
+                location: None,
+            };
+            storage
+                .commit(Some(user_id), &update)
+                .await
+                .wrap_err("failed to commit talks")?;
+            let _ = self.updates_sender.send(update);
+        }
+        let update = Update::RemoveLocation { location_id };
+        storage
+            .commit(Some(user_id), &update)
+            .await
+            .wrap_err("failed to commit locations")?;
+        let _ = self.updates_sender.send(update);
+        self.send_conflicts(&storage, &conflicts_before);
+        info!("User {user_id} deleted location {location_id}");
+        Ok(())
+    }
+
+    pub async fn propose_schedule(&self, user_id: UserId) -> Result<ScheduleProposal> {
+        let storage = self.storage.read().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_scheduler() {
+            bail!("only schedulers can propose schedules");
+        }
+        let windows = storage.event.active_windows()?;
+        let slot = storage.event.slot();
+        let talks = storage.talks.clone();
+        let locations = storage.locations.clone();
+        drop(storage);
+        spawn_blocking(move || propose_schedule(&talks, &locations, &windows, slot))
+            .await
+            .wrap_err("failed to join schedule solver task")
+    }
+
+    pub async fn update_draft(
+        &self,
+        user_id: UserId,
+        name: DraftName,
+        schedules: BTreeMap<usize, Option<TalkSchedule>>,
+    ) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_scheduler() {
+            bail!("only schedulers can edit drafts");
+        }
+        if name.trim().is_empty() {
+            bail!("draft name must not be empty");
+        }
+        let mut based_on = BTreeMap::new();
+        for (talk_id, schedule) in &schedules {
+            let Some(schedule) = schedule else {
+                continue;
+            };
+            let talk = storage
+                .talks
+                .get(talk_id)
+                .wrap_err_with(|| format!("talk {talk_id} does not exist"))?;
+            storage.event.check_schedule(&schedule.apply_to(talk))?;
+            if let Some(location_id) = schedule.location {
+                if !storage.locations.contains_key(&location_id) {
+                    bail!("location {location_id} does not exist");
+                }
+            }
+            based_on.insert(*talk_id, TalkSchedule::of(talk));
+        }
+        let updated_at = SystemTime::now();
+        let draft = storage.drafts.entry(name.clone()).or_insert_with(|| Draft {
+            talks: BTreeMap::new(),
+            based_on: BTreeMap::new(),
+            updated_by: user_id,
+            updated_at,
         });
+        for (talk_id, schedule) in schedules {
+            match schedule {
+                Some(schedule) => {
+                    draft.talks.insert(talk_id, schedule);
+                    draft.based_on.insert(talk_id, based_on[&talk_id]);
+                }
+                None => {
+                    draft.talks.remove(&talk_id);
+                    draft.based_on.remove(&talk_id);
+                }
+            }
+        }
+        draft.updated_by = user_id;
+        draft.updated_at = updated_at;
+        let update = Update::UpdateDraft {
+            name,
+            draft: Some(draft.clone()),
+        };
+        storage
+            .backend
+            .store_drafts(&storage.drafts)
+            .await
+            .wrap_err("failed to commit drafts")?;
+        let _ = self.updates_sender.send(update);
         Ok(())
     }
+
+    pub async fn delete_draft(&self, user_id: UserId, name: DraftName) -> Result<()> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_scheduler() {
+            bail!("only schedulers can delete drafts");
+        }
+        if storage.drafts.remove(&name).is_none() {
+            bail!("draft {name} does not exist");
+        }
+        storage
+            .backend
+            .store_drafts(&storage.drafts)
+            .await
+            .wrap_err("failed to commit drafts")?;
+        info!("User {user_id} deleted draft {name}");
+        let _ = self
+            .updates_sender
+            .send(Update::UpdateDraft { name, draft: None });
+        Ok(())
+    }
+
+    /// Reschedules all talks of the draft in a single update, so that nobody sees it half-applied.
+    ///
+    /// The draft is removed before the talks are rescheduled and restored if that fails, so that
+    /// it is never published twice. A failed commit leaves the talks as they were, so restoring
+    /// the draft is all there is to roll back.
+    pub async fn publish_draft(&self, user_id: UserId, name: DraftName) -> Result<Option<String>> {
+        let mut storage = self.storage.write().await;
+        let user = storage.users.get(&user_id).wrap_err("unknown user")?;
+        if !user.is_scheduler() {
+            bail!("only schedulers can publish drafts");
+        }
+        let draft = storage
+            .drafts
+            .get(&name)
+            .wrap_err_with(|| format!("draft {name} does not exist"))?;
+        let mut candidates = storage.talks.clone();
+        for (talk_id, schedule) in &draft.talks {
+            let talk = storage
+                .talks
+                .get(talk_id)
+                .wrap_err_with(|| format!("talk {talk_id} of the draft no longer exists"))?;
+            if draft
+                .based_on
+                .get(talk_id)
+                .is_some_and(|based_on| *based_on != TalkSchedule::of(talk))
+            {
+                bail!("talk {talk_id} changed since it was planned in draft {name}, plan it again");
+            }
+            if let Some(location_id) = schedule.location {
+                if !storage.locations.contains_key(&location_id) {
+                    bail!("location {location_id} of the draft no longer exists");
+                }
+            }
+            let candidate = schedule.apply_to(talk);
+            storage.event.check_schedule(&candidate)?;
+            candidates.insert(*talk_id, candidate);
+        }
+        let conflicts_before = find_conflicts(&storage.talks);
+        let new_conflicts: Vec<_> = find_conflicts(&candidates)
+            .difference(&conflicts_before)
+            .cloned()
+            .collect();
+        let warning = check_conflicts(
+            &storage,
+            &new_conflicts,
+            &format!("refusing to publish draft {name}"),
+        )?;
+
+        let draft = storage.drafts.remove(&name).expect("draft exists");
+        if let Err(error) = storage.backend.store_drafts(&storage.drafts).await {
+            storage.drafts.insert(name, draft);
+            return Err(error.wrap_err("failed to commit drafts"));
+        }
+        let update = Update::Reschedule {
+            schedules: draft.talks.clone(),
+        };
+        if let Err(error) = storage.commit(Some(user_id), &update).await {
+            // The talks are only rescheduled once the update is journaled, nothing was applied
+            storage.drafts.insert(name.clone(), draft);
+            if let Err(restore_error) = storage.backend.store_drafts(&storage.drafts).await {
+                error!("Failed to restore draft {name} after failing to publish it: {restore_error:#?}");
+            }
+            return Err(error.wrap_err("failed to commit talks"));
+        }
+        info!("User {user_id} published draft {name}");
+        let _ = self.updates_sender.send(update);
+        self.send_conflicts(&storage, &conflicts_before);
+        let _ = self
+            .updates_sender
+            .send(Update::UpdateDraft { name, draft: None });
+        Ok(warning)
+    }
+}
+
+/// Fails if the event rejects any of `new_conflicts`, otherwise describes them as warning.
+fn check_conflicts(
+    storage: &Storage,
+    new_conflicts: &[Conflict],
+    refusal: &str,
+) -> Result<Option<String>> {
+    let describe = |conflict: &Conflict| conflict.describe(&storage.users, &storage.locations);
+    let refused_conflicts: Vec<_> = new_conflicts
+        .iter()
+        .filter(|conflict| storage.event.conflict_policy(conflict) == ConflictPolicy::Reject)
+        .map(describe)
+        .collect();
+    if !refused_conflicts.is_empty() {
+        bail!("{refusal}: {}", refused_conflicts.join(", "));
+    }
+    Ok((!new_conflicts.is_empty()).then(|| {
+        new_conflicts
+            .iter()
+            .map(describe)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }))
+}
+
+fn validate_location(location: &Location) -> Result<()> {
+    if location.name.trim().is_empty() {
+        bail!("location name must not be empty");
+    }
+    for url in [&location.live_stream, &location.map_url]
+        .into_iter()
+        .flatten()
+    {
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            bail!("{url} is not an HTTP(S) URL");
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,321 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+
+use crate::{
+    conflicts::{find_conflicts, Conflict},
+    storage::{Location, Talk},
+};
+
+/// Maximum number of start times tried per talk, to bound the time the solver takes.
+const MAX_START_TIMES: usize = 10_000;
+
+/// A time span in which talks may take place, e.g. the active hours of a day.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeWindow {
+    pub start: SystemTime,
+    pub end: SystemTime,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Assignment {
+    pub scheduled_at: SystemTime,
+    pub location: usize,
+}
+
+/// Proposed schedule of the unscheduled talks, which is not applied by the solver.
+#[derive(Clone, Debug, Serialize)]
+pub struct ScheduleProposal {
+    pub assignments: BTreeMap<usize, Assignment>,
+    /// Talks for which no start time and location without conflicts was found.
+    pub unplaced: BTreeSet<usize>,
+    /// Conflicts remaining with the proposal applied, e.g. between talks scheduled before.
+    pub conflicts: BTreeSet<Conflict>,
+}
+
+/// Proposes start times and locations for the unscheduled talks.
+///
+/// Talks that are already scheduled stay where they are. The other talks are placed greedily,
+/// those with the most participants first, at the start time and location where they share the
+/// fewest participants with overlapping talks. Talks never share a location or a nerd with an
+/// overlapping talk, and are only placed at locations large enough for their participants. Talks
+/// that already have a location are only placed there.
+pub fn propose_schedule(
+    talks: &BTreeMap<usize, Talk>,
+    locations: &BTreeMap<usize, Location>,
+    windows: &[TimeWindow],
+    slot: Duration,
+) -> ScheduleProposal {
+    let mut placed: BTreeMap<usize, Talk> = talks
+        .iter()
+        .filter(|(_talk_id, talk)| talk.scheduled_at.is_some())
+        .map(|(talk_id, talk)| (*talk_id, talk.clone()))
+        .collect();
+    let mut unscheduled: Vec<&Talk> = talks
+        .values()
+        .filter(|talk| talk.scheduled_at.is_none())
+        .collect();
+    unscheduled.sort_by_key(|talk| {
+        (
+            std::cmp::Reverse(participant_count(talk)),
+            std::cmp::Reverse(talk.duration),
+            talk.id,
+        )
+    });
+
+    let mut assignments = BTreeMap::new();
+    let mut unplaced = BTreeSet::new();
+    for talk in unscheduled {
+        match best_assignment(talk, &placed, locations, windows, slot) {
+            Some(assignment) => {
+                let mut talk = talk.clone();
+                talk.scheduled_at = Some(assignment.scheduled_at);
+                talk.location = Some(assignment.location);
+                assignments.insert(talk.id, assignment);
+                placed.insert(talk.id, talk);
+            }
+            None => {
+                unplaced.insert(talk.id);
+            }
+        }
+    }
+
+    ScheduleProposal {
+        assignments,
+        unplaced,
+        conflicts: find_conflicts(&placed),
+    }
+}
+
+fn best_assignment(
+    talk: &Talk,
+    placed: &BTreeMap<usize, Talk>,
+    locations: &BTreeMap<usize, Location>,
+    windows: &[TimeWindow],
+    slot: Duration,
+) -> Option<Assignment> {
+    let participants = participant_count(talk);
+    let candidate_locations: Vec<usize> = locations
+        .values()
+        .filter(|location| {
+            talk.location
+                .is_none_or(|location_id| location_id == location.id)
+        })
+        .filter(|location| {
+            location
+                .capacity
+                .is_none_or(|capacity| participants <= capacity as usize)
+        })
+        .map(|location| location.id)
+        .collect();
+    if candidate_locations.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<(usize, Assignment)> = None;
+    for start in start_times(windows, talk.duration, slot) {
+        let Some(end) = start.checked_add(talk.duration) else {
+            continue;
+        };
+        let overlapping: Vec<&Talk> = placed
+            .values()
+            .filter(|other| {
+                other.scheduled_at.is_some_and(|other_start| {
+                    other_start
+                        .checked_add(other.duration)
+                        .is_none_or(|other_end| start < other_end)
+                        && other_start < end
+                })
+            })
+            .collect();
+        if overlapping
+            .iter()
+            .any(|other| !talk.nerds.is_disjoint(&other.nerds))
+        {
+            continue;
+        }
+        let cost: usize = overlapping
+            .iter()
+            .map(|other| shared_participants(talk, other))
+            .sum();
+        // Earlier start times win ties, so nothing can beat a start time without shared participants
+        if best.as_ref().is_some_and(|(best_cost, _)| *best_cost == 0) {
+            break;
+        }
+        if best
+            .as_ref()
+            .is_some_and(|(best_cost, _)| *best_cost <= cost)
+        {
+            continue;
+        }
+        let free_location = candidate_locations.iter().find(|location_id| {
+            overlapping
+                .iter()
+                .all(|other| other.location != Some(**location_id))
+        });
+        if let Some(location_id) = free_location {
+            best = Some((
+                cost,
+                Assignment {
+                    scheduled_at: start,
+                    location: *location_id,
+                },
+            ));
+        }
+    }
+    best.map(|(_cost, assignment)| assignment)
+}
+
+/// Start times in the windows at multiples of the slot after each window's start.
+fn start_times(
+    windows: &[TimeWindow],
+    duration: Duration,
+    slot: Duration,
+) -> impl Iterator<Item = SystemTime> + '_ {
+    let slot = slot.max(Duration::from_secs(60));
+    windows
+        .iter()
+        .flat_map(move |window| {
+            (0..)
+                .map_while(move |index| window.start.checked_add(slot.checked_mul(index)?))
+                .take_while(move |start| {
+                    start
+                        .checked_add(duration)
+                        .is_some_and(|end| end <= window.end)
+                })
+        })
+        .take(MAX_START_TIMES)
+}
+
+fn participant_count(talk: &Talk) -> usize {
+    talk.nerds.union(&talk.noobs).count()
+}
+
+fn shared_participants(talk: &Talk, other: &Talk) -> usize {
+    let participants: BTreeSet<_> = talk.nerds.union(&talk.noobs).collect();
+    other
+        .nerds
+        .union(&other.noobs)
+        .filter(|user_id| participants.contains(user_id))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{hour, talk, talks, HOUR};
+
+    fn locations(capacities: &[Option<u32>]) -> BTreeMap<usize, Location> {
+        capacities
+            .iter()
+            .enumerate()
+            .map(|(id, capacity)| {
+                let location = Location {
+                    id,
+                    name: format!("Location {id}"),
+                    live_stream: None,
+                    capacity: *capacity,
+                    building: None,
+                    floor: None,
+                    accessibility_notes: None,
+                    map_url: None,
+                };
+                (id, location)
+            })
+            .collect()
+    }
+
+    fn window(start_hour: u32, end_hour: u32) -> TimeWindow {
+        TimeWindow {
+            start: hour(start_hour),
+            end: hour(end_hour),
+        }
+    }
+
+    fn assignment(start_hour: u32, location: usize) -> (SystemTime, usize) {
+        (hour(start_hour), location)
+    }
+
+    fn placed(proposal: &ScheduleProposal, talk_id: usize) -> Option<(SystemTime, usize)> {
+        proposal
+            .assignments
+            .get(&talk_id)
+            .map(|assignment| (assignment.scheduled_at, assignment.location))
+    }
+
+    #[test]
+    fn scheduled_talks_stay_in_place() {
+        let talks = talks([talk(1, Some(10), Some(0), &[7]), talk(2, None, None, &[7])]);
+        let proposal = propose_schedule(&talks, &locations(&[None]), &[window(10, 12)], HOUR);
+        assert!(!proposal.assignments.contains_key(&1));
+        assert_eq!(placed(&proposal, 2), Some(assignment(11, 0)));
+        assert!(proposal.conflicts.is_empty());
+    }
+
+    #[test]
+    fn talks_are_only_placed_at_locations_fitting_their_participants() {
+        let mut large = talk(1, None, None, &[7]);
+        large.noobs = BTreeSet::from([8, 9]);
+        let talks = talks([large]);
+        let locations = locations(&[Some(2), None, Some(3)]);
+        let proposal = propose_schedule(&talks, &locations, &[window(10, 11)], HOUR);
+        assert_eq!(placed(&proposal, 1), Some(assignment(10, 1)));
+    }
+
+    #[test]
+    fn talks_sharing_a_nerd_or_location_do_not_overlap() {
+        let talks = talks([
+            talk(1, None, None, &[7]),
+            talk(2, None, None, &[7]),
+            talk(3, None, Some(1), &[8]),
+            talk(4, None, Some(1), &[9]),
+        ]);
+        let proposal = propose_schedule(&talks, &locations(&[None, None]), &[window(10, 12)], HOUR);
+        assert!(proposal.unplaced.is_empty());
+        assert!(proposal.conflicts.is_empty());
+        let start = |talk_id| placed(&proposal, talk_id).unwrap().0;
+        assert_ne!(start(1), start(2));
+        assert_ne!(start(3), start(4));
+    }
+
+    #[test]
+    fn talks_without_free_start_time_are_unplaced() {
+        let talks = talks([
+            talk(1, Some(10), Some(0), &[7]),
+            talk(2, None, None, &[7]),
+            talk(3, None, Some(0), &[8]),
+        ]);
+        let proposal = propose_schedule(&talks, &locations(&[None, None]), &[window(10, 11)], HOUR);
+        assert!(proposal.assignments.is_empty());
+        assert_eq!(proposal.unplaced, BTreeSet::from([2, 3]));
+    }
+
+    #[test]
+    fn earliest_start_time_without_shared_participants_wins() {
+        let mut fixed = talk(1, Some(10), Some(0), &[7]);
+        fixed.noobs = BTreeSet::from([9]);
+        let mut shared = talk(2, None, None, &[8]);
+        shared.noobs = BTreeSet::from([9]);
+        let talks = talks([fixed, shared]);
+        let proposal = propose_schedule(&talks, &locations(&[None, None]), &[window(10, 20)], HOUR);
+        assert_eq!(placed(&proposal, 2), Some(assignment(11, 0)));
+    }
+
+    #[test]
+    fn huge_durations_and_slots_do_not_panic() {
+        let mut long = talk(1, None, None, &[7]);
+        long.duration = Duration::MAX;
+        let talks = talks([long, talk(2, None, None, &[8])]);
+        let proposal = propose_schedule(
+            &talks,
+            &locations(&[None]),
+            &[window(10, 12)],
+            Duration::MAX,
+        );
+        assert_eq!(proposal.unplaced, BTreeSet::from([1]));
+        assert_eq!(placed(&proposal, 2), Some(assignment(10, 0)));
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use eyre::{eyre, Context as _};
+use rusqlite::{
+    params,
+    types::{FromSql, ToSql},
+    Connection, Transaction,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tokio::task::spawn_blocking;
+
+use crate::{
+    storage::{Draft, DraftName, InviteData, InviteId, TokenStore, User, UserId},
+    storage_backend::{RawCollections, StorageBackend},
+};
+
+pub const DATABASE_FILE: &str = "mopad.sqlite";
+
+const SCHEMA: &str = "
+PRAGMA journal_mode = WAL;
+CREATE TABLE IF NOT EXISTS teams (name TEXT PRIMARY KEY NOT NULL);
+CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY NOT NULL, data TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS locations (id INTEGER PRIMARY KEY NOT NULL, data TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS talks (id INTEGER PRIMARY KEY NOT NULL, data TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS tokens (token TEXT PRIMARY KEY NOT NULL, data TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS invites (id TEXT PRIMARY KEY NOT NULL, data TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS drafts (name TEXT PRIMARY KEY NOT NULL, data TEXT NOT NULL);
+";
+
+/// Stores every collection in a table of an embedded SQLite database.
+///
+/// Entries are kept as JSON in a `data` column so that the serde representation stays the single
+/// source of truth for the layout of users, locations and talks. Snapshots only write the rows
+/// that changed since the previous one.
+#[derive(Debug)]
+pub struct SqliteBackend {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteBackend {
+    pub async fn open(path: &Path) -> eyre::Result<Self> {
+        let path = path.join(DATABASE_FILE);
+        let connection = spawn_blocking(move || -> eyre::Result<_> {
+            let connection = Connection::open(&path)
+                .wrap_err_with(|| format!("failed to open database {}", path.display()))?;
+            connection
+                .execute_batch(SCHEMA)
+                .wrap_err("failed to create schema")?;
+            Ok(connection)
+        })
+        .await
+        .wrap_err("failed to join database task")??;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn with_connection<T>(
+        &self,
+        operation: impl FnOnce(&mut Connection) -> eyre::Result<T> + Send + 'static,
+    ) -> eyre::Result<T>
+    where
+        T: Send + 'static,
+    {
+        let connection = self.connection.clone();
+        spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .map_err(|_| eyre!("database connection is poisoned"))?;
+            operation(&mut connection)
+        })
+        .await
+        .wrap_err("failed to join database task")?
+    }
+
+    async fn store_entry<T>(
+        &self,
+        table: &'static str,
+        id: usize,
+        entry: Option<&T>,
+    ) -> eyre::Result<()>
+    where
+        T: Serialize,
+    {
+        let data = entry
+            .map(serde_json::to_string)
+            .transpose()
+            .wrap_err("failed to serialize entry")?;
+        self.with_connection(move |connection| {
+            match data {
+                Some(data) => connection.execute(
+                    &format!(
+                        "INSERT INTO {table} (id, data) VALUES (?1, ?2) \
+                         ON CONFLICT (id) DO UPDATE SET data = excluded.data"
+                    ),
+                    params![id, data],
+                ),
+                None => connection.execute(&format!("DELETE FROM {table} WHERE id = ?1"), [id]),
+            }
+            .wrap_err_with(|| format!("failed to store entry {id} in {table}"))?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+fn read_entries<T>(connection: &Connection, table: &str) -> eyre::Result<BTreeMap<usize, T>>
+where
+    T: DeserializeOwned,
+{
+    let mut statement = connection
+        .prepare(&format!("SELECT id, data FROM {table}"))
+        .wrap_err_with(|| format!("failed to prepare query for {table}"))?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, usize>(0)?, row.get::<_, String>(1)?))
+        })
+        .wrap_err_with(|| format!("failed to query {table}"))?;
+    rows.map(|row| {
+        let (id, data) = row.wrap_err_with(|| format!("failed to read row of {table}"))?;
+        let entry = serde_json::from_str(&data)
+            .wrap_err_with(|| format!("failed to deserialize entry {id} of {table}"))?;
+        Ok((id, entry))
+    })
+    .collect()
+}
+
+/// Reads the entries of a table keyed by text instead of ids.
+fn read_keyed_entries(
+    connection: &Connection,
+    table: &str,
+    key_column: &str,
+) -> eyre::Result<BTreeMap<String, Value>> {
+    connection
+        .prepare(&format!("SELECT {key_column}, data FROM {table}"))?
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .map(|row| {
+            let (key, data) = row.wrap_err_with(|| format!("failed to read row of {table}"))?;
+            let data = serde_json::from_str(&data)
+                .wrap_err_with(|| format!("failed to parse entry of {table}"))?;
+            Ok((key, data))
+        })
+        .collect()
+}
+
+impl StorageBackend for SqliteBackend {
+    async fn load_raw(&self) -> eyre::Result<RawCollections> {
+        self.with_connection(|connection| {
+            let teams = connection
+                .prepare("SELECT name FROM teams")?
+                .query_map([], |row| row.get(0))?
+                .collect::<Result<_, _>>()
+                .wrap_err("failed to read teams")?;
+            let users = read_entries(connection, "users")?;
+            let locations = read_entries(connection, "locations")?;
+            let talks = read_entries(connection, "talks")?;
+            let tokens = read_keyed_entries(connection, "tokens", "token")?;
+            let invites = read_keyed_entries(connection, "invites", "id")?;
+            let drafts = read_keyed_entries(connection, "drafts", "name")?;
+            Ok(RawCollections {
+                teams,
+                users,
+                locations,
+                talks,
+                tokens,
+                invites,
+                drafts,
+            })
+        })
+        .await
+    }
+
+    async fn store_user(
+        &self,
+        users: &BTreeMap<UserId, User>,
+        user_id: UserId,
+    ) -> eyre::Result<()> {
+        self.store_entry("users", user_id, users.get(&user_id))
+            .await
+    }
+
+    async fn store_tokens(&self, tokens: &TokenStore) -> eyre::Result<()> {
+        let tokens = serialize_tokens(tokens)?;
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            sync_entries(&transaction, "tokens", "token", tokens)?;
+            transaction.commit().wrap_err("failed to commit tokens")
+        })
+        .await
+    }
+
+    async fn store_invites(&self, invites: &BTreeMap<InviteId, InviteData>) -> eyre::Result<()> {
+        let invites = serialize_keyed_entries(invites).wrap_err("failed to serialize invites")?;
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            sync_entries(&transaction, "invites", "id", invites)?;
+            transaction.commit().wrap_err("failed to commit invites")
+        })
+        .await
+    }
+
+    async fn store_drafts(&self, drafts: &BTreeMap<DraftName, Draft>) -> eyre::Result<()> {
+        let drafts = serialize_keyed_entries(drafts).wrap_err("failed to serialize drafts")?;
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            sync_entries(&transaction, "drafts", "name", drafts)?;
+            transaction.commit().wrap_err("failed to commit drafts")
+        })
+        .await
+    }
+
+    async fn store_raw(&self, collections: &RawCollections) -> eyre::Result<()> {
+        let teams = collections.teams.clone();
+        let users = encode_entries(&collections.users);
+        let locations = encode_entries(&collections.locations);
+        let talks = encode_entries(&collections.talks);
+        let tokens = encode_keyed_entries(&collections.tokens);
+        let invites = encode_keyed_entries(&collections.invites);
+        let drafts = encode_keyed_entries(&collections.drafts);
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            sync_teams(&transaction, teams)?;
+            sync_entries(&transaction, "users", "id", users)?;
+            sync_entries(&transaction, "locations", "id", locations)?;
+            sync_entries(&transaction, "talks", "id", talks)?;
+            sync_entries(&transaction, "tokens", "token", tokens)?;
+            sync_entries(&transaction, "invites", "id", invites)?;
+            sync_entries(&transaction, "drafts", "name", drafts)?;
+            transaction
+                .commit()
+                .wrap_err("failed to commit collections")
+        })
+        .await
+    }
+}
+
+fn encode_entries(entries: &BTreeMap<usize, Value>) -> Vec<(usize, String)> {
+    entries
+        .iter()
+        .map(|(id, entry)| (*id, entry.to_string()))
+        .collect()
+}
+
+fn encode_keyed_entries(entries: &BTreeMap<String, Value>) -> Vec<(String, String)> {
+    entries
+        .iter()
+        .map(|(key, entry)| (key.clone(), entry.to_string()))
+        .collect()
+}
+
+fn serialize_tokens(tokens: &TokenStore) -> eyre::Result<Vec<(String, String)>> {
+    tokens
+        .iter()
+        .map(|(token, data)| Ok((token.clone(), serde_json::to_string(data)?)))
+        .collect::<Result<_, serde_json::Error>>()
+        .wrap_err("failed to serialize tokens")
+}
+
+fn serialize_keyed_entries<T>(
+    entries: &BTreeMap<String, T>,
+) -> serde_json::Result<Vec<(String, String)>>
+where
+    T: Serialize,
+{
+    entries
+        .iter()
+        .map(|(key, entry)| Ok((key.clone(), serde_json::to_string(entry)?)))
+        .collect()
+}
+
+/// Makes the table contain exactly the given entries, writing only rows whose data changed.
+fn sync_entries<K>(
+    transaction: &Transaction,
+    table: &str,
+    key_column: &str,
+    entries: Vec<(K, String)>,
+) -> eyre::Result<()>
+where
+    K: Debug + FromSql + Ord + ToSql,
+{
+    let mut stored = transaction
+        .prepare(&format!("SELECT {key_column}, data FROM {table}"))
+        .wrap_err_with(|| format!("failed to prepare query for {table}"))?
+        .query_map([], |row| {
+            Ok((row.get::<_, K>(0)?, row.get::<_, String>(1)?))
+        })
+        .wrap_err_with(|| format!("failed to query {table}"))?
+        .collect::<Result<BTreeMap<_, _>, _>>()
+        .wrap_err_with(|| format!("failed to read rows of {table}"))?;
+    let mut upsert = transaction
+        .prepare(&format!(
+            "INSERT INTO {table} ({key_column}, data) VALUES (?1, ?2) \
+             ON CONFLICT ({key_column}) DO UPDATE SET data = excluded.data"
+        ))
+        .wrap_err_with(|| format!("failed to prepare upsert into {table}"))?;
+    for (key, data) in entries {
+        if stored.remove(&key).as_ref() != Some(&data) {
+            upsert
+                .execute(params![key, data])
+                .wrap_err_with(|| format!("failed to store entry {key:?} in {table}"))?;
+        }
+    }
+    let mut delete = transaction
+        .prepare(&format!("DELETE FROM {table} WHERE {key_column} = ?1"))
+        .wrap_err_with(|| format!("failed to prepare delete from {table}"))?;
+    for key in stored.into_keys() {
+        delete
+            .execute([&key])
+            .wrap_err_with(|| format!("failed to delete entry {key:?} from {table}"))?;
+    }
+    Ok(())
+}
+
+fn sync_teams(transaction: &Transaction, teams: BTreeSet<String>) -> eyre::Result<()> {
+    let stored = transaction
+        .prepare("SELECT name FROM teams")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<BTreeSet<_>, _>>()
+        .wrap_err("failed to read teams")?;
+    for team in stored.difference(&teams) {
+        transaction
+            .execute("DELETE FROM teams WHERE name = ?1", [team])
+            .wrap_err_with(|| format!("failed to delete team {team}"))?;
+    }
+    for team in teams.difference(&stored) {
+        transaction
+            .execute("INSERT INTO teams (name) VALUES (?1)", [team])
+            .wrap_err_with(|| format!("failed to insert team {team}"))?;
+    }
+    Ok(())
+}
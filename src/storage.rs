@@ -1,60 +1,185 @@
 use core::fmt::Debug;
 use std::{
     collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
     path::PathBuf,
     time::{Duration, SystemTime},
 };
 
 use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use eyre::Context;
+use eyre::{bail, Context, ContextCompat as _};
 use rand_core::OsRng;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest as _, Sha256};
 use tokio::fs::{create_dir_all, try_exists};
 
-use crate::mirrored_to_disk::MirroredToDisk;
+use tracing::{info, warn};
+
+use crate::{
+    event::Event,
+    journal::{Journal, JournalEntry},
+    json_backend::{JsonBackend, TEAMS_FILE},
+    messages::Update,
+    migration::migrate,
+    storage_backend::{Backend, BackendKind, Collections, RawCollections, StorageBackend as _},
+};
 
 pub type Token = String;
 
+/// SHA-256 of a token as hex, the only form in which tokens are stored.
+pub type TokenHash = String;
+
+pub fn hash_token(token: &str) -> TokenHash {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+pub fn generate_session_id() -> String {
+    SaltString::generate(&mut OsRng).to_string()
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TokenData {
     pub user_id: usize,
     pub expires_at: SystemTime,
+    /// Identifies the session without revealing the token, e.g. for revoking it.
+    pub session_id: String,
+    pub created_at: SystemTime,
+    pub last_used_at: SystemTime,
+    pub user_agent: Option<String>,
+    pub remote_address: Option<SocketAddr>,
 }
 
+/// Sessions by the hash of their token, so that reading the storage does not reveal any token.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct TokenStore {
     #[serde(flatten)]
-    store: BTreeMap<Token, TokenData>,
+    store: BTreeMap<TokenHash, TokenData>,
 }
 
 impl TokenStore {
-    pub fn insert(&mut self, token: Token, user_id: usize, expires_at: SystemTime) {
-        self.store.insert(
-            token,
-            TokenData {
-                user_id,
-                expires_at,
-            },
-        );
+    pub fn insert(&mut self, token: &Token, data: TokenData) {
+        self.store.insert(hash_token(token), data);
     }
 
     pub fn remove_expired(&mut self, now: SystemTime) {
         self.store.retain(|_token, data| data.expires_at >= now);
     }
 
-    pub fn get(&self, token: &Token) -> Option<&TokenData> {
-        self.store.get(token)
+    pub fn find_session(&self, session_id: &str) -> Option<&TokenData> {
+        self.store
+            .values()
+            .find(|data| data.session_id == session_id)
+    }
+
+    /// Lets the token expire at the given time at the latest, returning its previous data.
+    pub fn expire_at(&mut self, token: &Token, expires_at: SystemTime) -> Option<TokenData> {
+        let data = self.store.get_mut(&hash_token(token))?;
+        let previous_data = data.clone();
+        data.expires_at = data.expires_at.min(expires_at);
+        Some(previous_data)
+    }
+
+    /// Removes all tokens of the session, also replaced ones that are still valid for a while.
+    pub fn remove_session(&mut self, session_id: &str) {
+        self.store
+            .retain(|_token, data| data.session_id != session_id);
+    }
+
+    /// Removes all tokens of the user, returning how many there were.
+    pub fn remove_user(&mut self, user_id: UserId) -> usize {
+        let count = self.store.len();
+        self.store.retain(|_token, data| data.user_id != user_id);
+        count - self.store.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&TokenHash, &TokenData)> {
+        self.store.iter()
+    }
+}
+
+impl FromIterator<(TokenHash, TokenData)> for TokenStore {
+    fn from_iter<I: IntoIterator<Item = (TokenHash, TokenData)>>(iter: I) -> Self {
+        Self {
+            store: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Identifies an invite without revealing its code, e.g. for revoking it.
+pub type InviteId = String;
+
+/// An invite code that lets anyone who knows it register into the team, until it is revoked.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct InviteData {
+    pub team: String,
+    pub code_hash: TokenHash,
+    pub created_by: UserId,
+    pub created_at: SystemTime,
+}
+
+pub type DraftName = String;
+
+/// Unpublished changes to the schedule, which schedulers work on together until one publishes it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Draft {
+    /// Planned schedule by talk id, talks without one keep their current schedule.
+    pub talks: BTreeMap<usize, TalkSchedule>,
+    /// Schedule of each planned talk when it was last planned, to notice changes made since.
+    #[serde(default)]
+    pub based_on: BTreeMap<usize, TalkSchedule>,
+    pub updated_by: UserId,
+    pub updated_at: SystemTime,
+}
+
+/// When, for how long and where a talk takes place.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TalkSchedule {
+    pub scheduled_at: Option<SystemTime>,
+    pub duration: Duration,
+    pub location: Option<usize>,
+}
+
+impl TalkSchedule {
+    /// The current schedule of the talk.
+    pub fn of(talk: &Talk) -> Self {
+        Self {
+            scheduled_at: talk.scheduled_at,
+            duration: talk.duration,
+            location: talk.location,
+        }
+    }
+
+    /// The talk rescheduled accordingly.
+    pub fn apply_to(&self, talk: &Talk) -> Talk {
+        Talk {
+            scheduled_at: self.scheduled_at,
+            duration: self.duration,
+            location: self.location,
+            ..talk.clone()
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct Storage {
     pub path: PathBuf,
-    pub teams: MirroredToDisk<BTreeSet<String>>,
-    pub users: MirroredToDisk<BTreeMap<UserId, User>>,
-    pub locations: MirroredToDisk<BTreeMap<usize, Location>>,
-    pub talks: MirroredToDisk<BTreeMap<usize, Talk>>,
-    pub tokens: MirroredToDisk<TokenStore>,
+    pub backend: Backend,
+    pub journal: Journal,
+    pub teams: BTreeSet<String>,
+    pub users: BTreeMap<UserId, User>,
+    pub locations: BTreeMap<usize, Location>,
+    pub talks: BTreeMap<usize, Talk>,
+    pub tokens: TokenStore,
+    pub invites: BTreeMap<InviteId, InviteData>,
+    pub drafts: BTreeMap<DraftName, Draft>,
+    pub event: Event,
+    /// Teams, users, locations and talks as last read from or written to the backend, to tell
+    /// hand edits apart from changes that are only recorded in the journal.
+    stored: RawCollections,
 }
 
 impl Storage {
@@ -63,7 +188,7 @@ impl Storage {
         name: String,
         team: String,
         attendance_mode: AttendanceMode,
-        password: String,
+        hash: String,
     ) -> UserId {
         let new_id = self
             .users
@@ -71,103 +196,435 @@ impl Storage {
             .max()
             .map(|id| id + 1)
             .unwrap_or_else(|| 0);
-        let user = User::new(new_id, name, team, attendance_mode, password);
+        let user = User::new(new_id, name, team, attendance_mode, hash);
         self.users.insert(new_id, user);
         new_id
     }
 
-    pub async fn load(path: impl Into<PathBuf> + Debug) -> eyre::Result<Self> {
-        let path = path.into();
-        let exists = try_exists(&path)
+    /// Records the update in the journal and applies it to the in-memory state.
+    ///
+    /// The update is applied to copies first, so that the state only changes once the update is
+    /// both valid and recorded.
+    pub async fn commit(&mut self, user_id: Option<UserId>, update: &Update) -> eyre::Result<()> {
+        let mut teams = self.teams.clone();
+        let mut users = self.users.clone();
+        let mut locations = self.locations.clone();
+        let mut talks = self.talks.clone();
+        apply_update(&mut teams, &mut users, &mut locations, &mut talks, update)?;
+        self.journal
+            .append(&JournalEntry {
+                timestamp: SystemTime::now(),
+                user_id,
+                update: update.clone(),
+            })
+            .await?;
+        self.teams = teams;
+        self.users = users;
+        self.locations = locations;
+        self.talks = talks;
+        Ok(())
+    }
+
+    /// Writes the current state as a snapshot to the backend if the journal is not empty.
+    pub async fn compact(&mut self) -> eyre::Result<()> {
+        if self.journal.is_empty().await? {
+            return Ok(());
+        }
+        self.store_snapshot().await?;
+        info!("Compacted journal into snapshot");
+        Ok(())
+    }
+
+    /// Writes the current state as a snapshot to the backend and archives the journal.
+    pub async fn store_snapshot(&mut self) -> eyre::Result<()> {
+        let collections = RawCollections::serialize(&Collections {
+            teams: self.teams.clone(),
+            users: self.users.clone(),
+            locations: self.locations.clone(),
+            talks: self.talks.clone(),
+            tokens: self.tokens.clone(),
+            invites: self.invites.clone(),
+            drafts: self.drafts.clone(),
+        })?;
+        self.backend
+            .store_raw(&collections)
             .await
-            .wrap_err("failed to check if storage directory exists")?;
-        if !exists {
-            create_dir_all(&path)
+            .wrap_err("failed to store snapshot")?;
+        self.stored = collections;
+        if !self.journal.is_empty().await? {
+            self.journal
+                .archive(SystemTime::now())
                 .await
-                .wrap_err("failed to create storage directory")?;
+                .wrap_err("failed to archive journal")?;
         }
-        let mut teams = MirroredToDisk::<BTreeSet<String>>::read_from_or_create_default(
-            path.join("teams.json"),
-        )
-        .await
-        .wrap_err("failed to read teams.json")?;
+        Ok(())
+    }
 
-        let mut users = MirroredToDisk::<BTreeMap<usize, User>>::read_from_or_create_default(
-            path.join("users.json"),
-        )
-        .await
-        .wrap_err("failed to read users")?;
-
-        for (user_id, user) in users.iter_mut() {
-            if *user_id != user.id {
-                tracing::warn!(
-                    "Inconsistent user id for user {}: key is {}, but user.id is {}. Syncing user.id to {}.",
-                    user.name,
-                    user_id,
-                    user.id,
+    /// Writes the user directly to the backend, bypassing the journal.
+    pub async fn store_user(&mut self, user_id: UserId) -> eyre::Result<()> {
+        self.backend.store_user(&self.users, user_id).await?;
+        match self.users.get(&user_id) {
+            Some(user) => {
+                self.stored.users.insert(
                     user_id,
+                    serde_json::to_value(user).wrap_err("failed to serialize user")?,
                 );
-                user.id = *user_id;
             }
-            if !teams.value.contains(&user.team) {
-                tracing::warn!(
-                    "User {} has unknown team {}. Assigning to 'Unknown' team.",
-                    user.name,
-                    user.team
-                );
-                user.team = "Unknown".to_string();
-                teams.value.insert("Unknown".to_string());
+            None => {
+                self.stored.users.remove(&user_id);
             }
         }
+        Ok(())
+    }
 
-        let locations = MirroredToDisk::<BTreeMap<usize, Location>>::read_from_or_create_default(
-            path.join("locations.json"),
-        )
-        .await?;
-
-        let mut talks = MirroredToDisk::<BTreeMap<usize, Talk>>::read_from_or_create_default(
-            path.join("talks.json"),
-        )
-        .await?;
-        let tokens = MirroredToDisk::read_from_or_create_default(path.join("tokens.json")).await?;
-
-        let user_ids: BTreeSet<usize> = users.keys().copied().collect();
-
-        talks.value.retain(|talk_id, talk| {
-            if !user_ids.contains(&talk.creator) {
-                tracing::warn!(
-                    "Dropping orphan talk {talk_id} (creator {} missing)",
-                    talk.creator
-                );
-                return false;
-            }
-
-            let original_nerds = talk.nerds.len();
-            talk.nerds.retain(|id| user_ids.contains(id));
-            if talk.nerds.len() != original_nerds {
-                tracing::info!("Cleaned invalid nerds from talk {talk_id}");
+    /// Reads the teams, users, locations and talks from the backend and applies the entries that
+    /// were edited by hand since the server last read or wrote them.
+    ///
+    /// Hand edits win over changes to the same entries that are only recorded in the journal, all
+    /// other entries keep their current state. Returns `None` if nothing was edited.
+    pub async fn read_hand_edits(&self) -> eyre::Result<Option<Collections>> {
+        let on_disk = self
+            .backend
+            .load_raw()
+            .await
+            .wrap_err("failed to load collections")?;
+        let mut current = RawCollections::serialize(&Collections {
+            teams: self.teams.clone(),
+            users: self.users.clone(),
+            locations: self.locations.clone(),
+            talks: self.talks.clone(),
+            ..Default::default()
+        })?;
+        let mut edited = false;
+        for team in on_disk.teams.symmetric_difference(&self.stored.teams) {
+            edited = true;
+            if on_disk.teams.contains(team) {
+                current.teams.insert(team.clone());
+            } else {
+                current.teams.remove(team);
             }
+        }
+        edited |= merge_hand_edits(&mut current.users, &on_disk.users, &self.stored.users);
+        edited |= merge_hand_edits(
+            &mut current.locations,
+            &on_disk.locations,
+            &self.stored.locations,
+        );
+        edited |= merge_hand_edits(&mut current.talks, &on_disk.talks, &self.stored.talks);
+        if !edited {
+            return Ok(None);
+        }
+        let mut collections = current
+            .deserialize()
+            .wrap_err("failed to deserialize edited collections")?;
+        repair(&mut collections);
+        Ok(Some(collections))
+    }
 
-            let original_noobs = talk.noobs.len();
-            talk.noobs.retain(|id| user_ids.contains(id));
-            if talk.noobs.len() != original_noobs {
-                tracing::info!("Cleaned invalid noobs from talk {talk_id}");
+    pub async fn load(
+        path: impl Into<PathBuf> + Debug,
+        backend_kind: BackendKind,
+    ) -> eyre::Result<Self> {
+        let path = path.into();
+        let exists = try_exists(&path)
+            .await
+            .wrap_err("failed to check if storage directory exists")?;
+        if !exists {
+            create_dir_all(&path)
+                .await
+                .wrap_err("failed to create storage directory")?;
+        }
+        let backend = Backend::open(backend_kind, &path)
+            .await
+            .wrap_err("failed to open storage backend")?;
+        if backend.kind() == BackendKind::Sqlite
+            && backend
+                .load_raw()
+                .await
+                .wrap_err("failed to load collections")?
+                .is_empty()
+            && try_exists(path.join(TEAMS_FILE))
+                .await
+                .wrap_err("failed to check if JSON storage exists")?
+        {
+            info!("Importing JSON storage into empty SQLite database");
+            let collections = JsonBackend::new(&path)
+                .load_raw()
+                .await
+                .wrap_err("failed to load JSON storage")?;
+            backend
+                .store_raw(&collections)
+                .await
+                .wrap_err("failed to import JSON storage")?;
+        }
+        let event = Event::load(&path).await.wrap_err("failed to load event")?;
+        let journal = Journal::new(&path);
+        migrate(&path, &backend, &journal)
+            .await
+            .wrap_err("failed to migrate storage")?;
+        let stored = backend
+            .load_raw()
+            .await
+            .wrap_err("failed to load collections")?;
+        let mut collections = stored
+            .clone()
+            .deserialize()
+            .wrap_err("failed to deserialize collections")?;
+        let entries = journal.read().await.wrap_err("failed to read journal")?;
+        if !entries.is_empty() {
+            info!("Replaying {} journal entries", entries.len());
+        }
+        for entry in entries {
+            if let Err(error) = collections.apply(&entry.update) {
+                warn!(
+                    "Skipping journal entry from {:?}: {error:#}",
+                    entry.timestamp
+                );
             }
-
-            true
-        });
+        }
+        repair(&mut collections);
+        let Collections {
+            teams,
+            users,
+            locations,
+            talks,
+            tokens,
+            invites,
+            drafts,
+        } = collections;
 
         Ok(Self {
             path: path.to_path_buf(),
+            backend,
+            journal,
             teams,
             users,
             locations,
             talks,
             tokens,
+            invites,
+            drafts,
+            event,
+            stored: RawCollections {
+                teams: stored.teams,
+                users: stored.users,
+                locations: stored.locations,
+                talks: stored.talks,
+                ..Default::default()
+            },
         })
     }
 }
 
+/// Replaces the entries that differ between what is on disk and what the server stored.
+fn merge_hand_edits<K>(
+    current: &mut BTreeMap<K, Value>,
+    on_disk: &BTreeMap<K, Value>,
+    stored: &BTreeMap<K, Value>,
+) -> bool
+where
+    K: Clone + Ord,
+{
+    let mut edited = false;
+    for key in on_disk.keys().chain(stored.keys()) {
+        let on_disk_entry = on_disk.get(key);
+        if on_disk_entry == stored.get(key) {
+            continue;
+        }
+        edited = true;
+        match on_disk_entry {
+            Some(entry) => current.insert(key.clone(), entry.clone()),
+            None => current.remove(key),
+        };
+    }
+    edited
+}
+
+/// Fixes inconsistencies between the collections, e.g. after a hand edit.
+fn repair(collections: &mut Collections) {
+    let Collections {
+        teams,
+        users,
+        talks,
+        ..
+    } = collections;
+    for (user_id, user) in users.iter_mut() {
+        if *user_id != user.id {
+            tracing::warn!(
+                "Inconsistent user id for user {}: key is {}, but user.id is {}. Syncing user.id to {}.",
+                user.name,
+                user_id,
+                user.id,
+                user_id,
+            );
+            user.id = *user_id;
+        }
+        if !teams.contains(&user.team) {
+            tracing::warn!(
+                "User {} has unknown team {}. Adding the team again.",
+                user.name,
+                user.team
+            );
+            teams.insert(user.team.clone());
+        }
+    }
+
+    let user_ids: BTreeSet<usize> = users.keys().copied().collect();
+
+    talks.retain(|talk_id, talk| {
+        if !user_ids.contains(&talk.creator) {
+            tracing::warn!(
+                "Dropping orphan talk {talk_id} (creator {} missing)",
+                talk.creator
+            );
+            return false;
+        }
+
+        let original_nerds = talk.nerds.len();
+        talk.nerds.retain(|id| user_ids.contains(id));
+        if talk.nerds.len() != original_nerds {
+            tracing::info!("Cleaned invalid nerds from talk {talk_id}");
+        }
+
+        let original_noobs = talk.noobs.len();
+        talk.noobs.retain(|id| user_ids.contains(id));
+        if talk.noobs.len() != original_noobs {
+            tracing::info!("Cleaned invalid noobs from talk {talk_id}");
+        }
+
+        true
+    });
+}
+
+impl Collections {
+    pub fn apply(&mut self, update: &Update) -> eyre::Result<()> {
+        apply_update(
+            &mut self.teams,
+            &mut self.users,
+            &mut self.locations,
+            &mut self.talks,
+            update,
+        )
+    }
+}
+
+fn apply_update(
+    teams: &mut BTreeSet<String>,
+    users: &mut BTreeMap<UserId, User>,
+    locations: &mut BTreeMap<usize, Location>,
+    talks: &mut BTreeMap<usize, Talk>,
+    update: &Update,
+) -> eyre::Result<()> {
+    fn talk(talks: &mut BTreeMap<usize, Talk>, talk_id: usize) -> eyre::Result<&mut Talk> {
+        talks
+            .get_mut(&talk_id)
+            .wrap_err_with(|| format!("talk {talk_id} does not exist"))
+    }
+
+    match update {
+        Update::Snapshot { .. }
+        | Update::Users { .. }
+        | Update::Conflicts { .. }
+        | Update::UpdateDraft { .. }
+        | Update::UpdateEvent { .. } => {}
+        Update::AddTalk { talk } => {
+            talks.insert(talk.id, talk.clone());
+        }
+        Update::RemoveTalk { talk_id } => {
+            talks.remove(talk_id);
+        }
+        Update::UpdateCreator { talk_id, creator } => {
+            talk(talks, *talk_id)?.creator = *creator;
+        }
+        Update::UpdateTitle { talk_id, title } => {
+            talk(talks, *talk_id)?.title = title.clone();
+        }
+        Update::UpdateDescription {
+            talk_id,
+            description,
+        } => {
+            talk(talks, *talk_id)?.description = description.clone();
+        }
+        Update::UpdateHighlight { talk_id, highlight } => {
+            talk(talks, *talk_id)?.highlight = *highlight;
+        }
+        Update::UpdateScheduledAt {
+            talk_id,
+            scheduled_at,
+        } => {
+            talk(talks, *talk_id)?.scheduled_at = *scheduled_at;
+        }
+        Update::UpdateDuration { talk_id, duration } => {
+            talk(talks, *talk_id)?.duration = *duration;
+        }
+        Update::UpdateLocation { talk_id, location } => {
+            talk(talks, *talk_id)?.location = *location;
+        }
+        Update::Reschedule { schedules } => {
+            // Check all talks first to apply either all schedules or none
+            if let Some(talk_id) = schedules
+                .keys()
+                .find(|talk_id| !talks.contains_key(talk_id))
+            {
+                bail!("talk {talk_id} does not exist");
+            }
+            for (talk_id, schedule) in schedules {
+                let talk = talk(talks, *talk_id)?;
+                *talk = schedule.apply_to(talk);
+            }
+        }
+        Update::AddNoob { talk_id, user_id } => {
+            talk(talks, *talk_id)?.noobs.insert(*user_id);
+        }
+        Update::RemoveNoob { talk_id, user_id } => {
+            talk(talks, *talk_id)?.noobs.remove(user_id);
+        }
+        Update::AddNerd { talk_id, user_id } => {
+            talk(talks, *talk_id)?.nerds.insert(*user_id);
+        }
+        Update::RemoveNerd { talk_id, user_id } => {
+            talk(talks, *talk_id)?.nerds.remove(user_id);
+        }
+        Update::UpdateAttendanceMode {
+            user_id,
+            attendance_mode,
+        } => {
+            users
+                .get_mut(user_id)
+                .wrap_err_with(|| format!("user {user_id} does not exist"))?
+                .attendance_mode = *attendance_mode;
+        }
+        Update::UpdateRoles { user_id, roles } => {
+            users
+                .get_mut(user_id)
+                .wrap_err_with(|| format!("user {user_id} does not exist"))?
+                .roles = roles.clone();
+        }
+        Update::AddLocation { location } | Update::ChangeLocation { location } => {
+            locations.insert(location.id, location.clone());
+        }
+        Update::RemoveLocation { location_id } => {
+            locations.remove(location_id);
+        }
+        Update::AddTeam { team } => {
+            teams.insert(team.clone());
+        }
+        Update::RemoveTeam { team } => {
+            teams.remove(team);
+        }
+        Update::RenameTeam { team, new_name } => {
+            if !teams.remove(team) {
+                bail!("team {team} does not exist");
+            }
+            teams.insert(new_name.clone());
+            for user in users.values_mut().filter(|user| &user.team == team) {
+                user.team = new_name.clone();
+            }
+        }
+    }
+    Ok(())
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum AttendanceMode {
     OnSite,
@@ -184,6 +641,31 @@ pub struct User {
     pub attendance_mode: AttendanceMode,
     pub hash: String,
     pub roles: BTreeSet<Role>,
+    pub password_reset: Option<PasswordReset>,
+}
+
+/// A pending password reset, redeemable once with the token before it expires.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PasswordReset {
+    pub token_hash: TokenHash,
+    pub expires_at: SystemTime,
+}
+
+/// Hashes the password with Argon2, which takes a while, so better call it on a blocking thread.
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
+/// Verifies the password against an Argon2 hash, better call it on a blocking thread as well.
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    let stored_hash = PasswordHash::new(hash).unwrap();
+    Argon2::default()
+        .verify_password(password.as_bytes(), &stored_hash)
+        .is_ok()
 }
 
 impl User {
@@ -192,28 +674,21 @@ impl User {
         name: String,
         team: String,
         attendance_mode: AttendanceMode,
-        password: String,
+        hash: String,
     ) -> Self {
-        let salt = SaltString::generate(&mut OsRng);
-        let hash = Argon2::default()
-            .hash_password(password.as_bytes(), &salt)
-            .unwrap();
-
         Self {
             id,
             name,
             team,
             attendance_mode,
-            hash: hash.to_string(),
+            hash,
             roles: BTreeSet::new(),
+            password_reset: None,
         }
     }
 
-    pub fn verify(&self, password: String) -> bool {
-        let stored_hash = PasswordHash::new(&self.hash).unwrap();
-        Argon2::default()
-            .verify_password(password.as_bytes(), &stored_hash)
-            .is_ok()
+    pub fn is_admin(&self) -> bool {
+        self.roles.contains(&Role::Admin)
     }
 
     pub fn is_editor(&self) -> bool {
@@ -231,6 +706,7 @@ impl User {
 
 #[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum Role {
+    Admin,
     Editor,
     Scheduler,
 }
@@ -249,9 +725,15 @@ pub struct Talk {
     pub noobs: BTreeSet<usize>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Location {
     pub id: usize,
     pub name: String,
     pub live_stream: Option<String>,
+    /// Number of people fitting into the location.
+    pub capacity: Option<u32>,
+    pub building: Option<String>,
+    pub floor: Option<String>,
+    pub accessibility_notes: Option<String>,
+    pub map_url: Option<String>,
 }
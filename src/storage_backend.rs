@@ -0,0 +1,213 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    path::Path,
+};
+
+use clap::ValueEnum;
+use eyre::Context as _;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    json_backend::JsonBackend,
+    sqlite_backend::SqliteBackend,
+    storage::{
+        Draft, DraftName, InviteData, InviteId, Location, Talk, TokenHash, TokenStore, User, UserId,
+    },
+};
+
+/// All collections persisted by a storage backend.
+#[derive(Debug, Default)]
+pub struct Collections {
+    pub teams: BTreeSet<String>,
+    pub users: BTreeMap<UserId, User>,
+    pub locations: BTreeMap<usize, Location>,
+    pub talks: BTreeMap<usize, Talk>,
+    pub tokens: TokenStore,
+    pub invites: BTreeMap<InviteId, InviteData>,
+    pub drafts: BTreeMap<DraftName, Draft>,
+}
+
+/// All collections with their entries as plain JSON values.
+///
+/// Migrations operate on this representation because older layouts of the entries may not
+/// deserialize into the current types anymore.
+#[derive(Clone, Debug, Default)]
+pub struct RawCollections {
+    pub teams: BTreeSet<String>,
+    pub users: BTreeMap<UserId, Value>,
+    pub locations: BTreeMap<usize, Value>,
+    pub talks: BTreeMap<usize, Value>,
+    pub tokens: BTreeMap<TokenHash, Value>,
+    pub invites: BTreeMap<InviteId, Value>,
+    pub drafts: BTreeMap<DraftName, Value>,
+}
+
+impl RawCollections {
+    pub fn is_empty(&self) -> bool {
+        self.teams.is_empty()
+            && self.users.is_empty()
+            && self.locations.is_empty()
+            && self.talks.is_empty()
+    }
+
+    pub fn serialize(collections: &Collections) -> eyre::Result<Self> {
+        Ok(Self {
+            teams: collections.teams.clone(),
+            users: serialize_entries(&collections.users).wrap_err("failed to serialize users")?,
+            locations: serialize_entries(&collections.locations)
+                .wrap_err("failed to serialize locations")?,
+            talks: serialize_entries(&collections.talks).wrap_err("failed to serialize talks")?,
+            tokens: collections
+                .tokens
+                .iter()
+                .map(|(token, data)| Ok((token.clone(), serde_json::to_value(data)?)))
+                .collect::<Result<_, serde_json::Error>>()
+                .wrap_err("failed to serialize tokens")?,
+            invites: serialize_entries(&collections.invites)
+                .wrap_err("failed to serialize invites")?,
+            drafts: serialize_entries(&collections.drafts)
+                .wrap_err("failed to serialize drafts")?,
+        })
+    }
+
+    pub fn deserialize(self) -> eyre::Result<Collections> {
+        Ok(Collections {
+            teams: self.teams,
+            users: deserialize_entries(self.users).wrap_err("failed to deserialize users")?,
+            locations: deserialize_entries(self.locations)
+                .wrap_err("failed to deserialize locations")?,
+            talks: deserialize_entries(self.talks).wrap_err("failed to deserialize talks")?,
+            tokens: self
+                .tokens
+                .into_iter()
+                .map(|(token, data)| Ok((token, serde_json::from_value(data)?)))
+                .collect::<Result<_, serde_json::Error>>()
+                .wrap_err("failed to deserialize tokens")?,
+            invites: deserialize_entries(self.invites).wrap_err("failed to deserialize invites")?,
+            drafts: deserialize_entries(self.drafts).wrap_err("failed to deserialize drafts")?,
+        })
+    }
+}
+
+fn serialize_entries<K, T>(entries: &BTreeMap<K, T>) -> serde_json::Result<BTreeMap<K, Value>>
+where
+    K: Clone + Ord,
+    T: Serialize,
+{
+    entries
+        .iter()
+        .map(|(key, entry)| Ok((key.clone(), serde_json::to_value(entry)?)))
+        .collect()
+}
+
+fn deserialize_entries<K, T>(entries: BTreeMap<K, Value>) -> eyre::Result<BTreeMap<K, T>>
+where
+    K: Debug + Ord,
+    T: DeserializeOwned,
+{
+    entries
+        .into_iter()
+        .map(|(key, entry)| {
+            let entry = serde_json::from_value(entry)
+                .wrap_err_with(|| format!("failed to deserialize entry {key:?}"))?;
+            Ok((key, entry))
+        })
+        .collect()
+}
+
+/// Persistence layer behind `Storage`.
+///
+/// Changes to talks are recorded in the journal and only reach the backend when the journal is
+/// compacted into a snapshot with `store_raw`. Users, tokens, invites and drafts are written
+/// directly because they carry data that is never broadcast to everyone as an `Update`.
+pub trait StorageBackend {
+    async fn load_raw(&self) -> eyre::Result<RawCollections>;
+    /// Replaces everything stored in the backend with the given collections.
+    async fn store_raw(&self, collections: &RawCollections) -> eyre::Result<()>;
+    /// Writes only the given user, or removes it if it is not in `users`.
+    async fn store_user(&self, users: &BTreeMap<UserId, User>, user_id: UserId)
+        -> eyre::Result<()>;
+    async fn store_tokens(&self, tokens: &TokenStore) -> eyre::Result<()>;
+    async fn store_invites(&self, invites: &BTreeMap<InviteId, InviteData>) -> eyre::Result<()>;
+    async fn store_drafts(&self, drafts: &BTreeMap<DraftName, Draft>) -> eyre::Result<()>;
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum BackendKind {
+    /// One JSON file per collection, rewritten on every change.
+    Json,
+    /// Embedded SQLite database, updated per entry.
+    Sqlite,
+}
+
+#[derive(Debug)]
+pub enum Backend {
+    Json(JsonBackend),
+    Sqlite(SqliteBackend),
+}
+
+impl Backend {
+    pub async fn open(kind: BackendKind, path: &Path) -> eyre::Result<Self> {
+        Ok(match kind {
+            BackendKind::Json => Self::Json(JsonBackend::new(path)),
+            BackendKind::Sqlite => Self::Sqlite(SqliteBackend::open(path).await?),
+        })
+    }
+
+    pub fn kind(&self) -> BackendKind {
+        match self {
+            Self::Json(_) => BackendKind::Json,
+            Self::Sqlite(_) => BackendKind::Sqlite,
+        }
+    }
+}
+
+impl StorageBackend for Backend {
+    async fn load_raw(&self) -> eyre::Result<RawCollections> {
+        match self {
+            Self::Json(backend) => backend.load_raw().await,
+            Self::Sqlite(backend) => backend.load_raw().await,
+        }
+    }
+
+    async fn store_raw(&self, collections: &RawCollections) -> eyre::Result<()> {
+        match self {
+            Self::Json(backend) => backend.store_raw(collections).await,
+            Self::Sqlite(backend) => backend.store_raw(collections).await,
+        }
+    }
+
+    async fn store_user(
+        &self,
+        users: &BTreeMap<UserId, User>,
+        user_id: UserId,
+    ) -> eyre::Result<()> {
+        match self {
+            Self::Json(backend) => backend.store_user(users, user_id).await,
+            Self::Sqlite(backend) => backend.store_user(users, user_id).await,
+        }
+    }
+
+    async fn store_tokens(&self, tokens: &TokenStore) -> eyre::Result<()> {
+        match self {
+            Self::Json(backend) => backend.store_tokens(tokens).await,
+            Self::Sqlite(backend) => backend.store_tokens(tokens).await,
+        }
+    }
+
+    async fn store_invites(&self, invites: &BTreeMap<InviteId, InviteData>) -> eyre::Result<()> {
+        match self {
+            Self::Json(backend) => backend.store_invites(invites).await,
+            Self::Sqlite(backend) => backend.store_invites(invites).await,
+        }
+    }
+
+    async fn store_drafts(&self, drafts: &BTreeMap<DraftName, Draft>) -> eyre::Result<()> {
+        match self {
+            Self::Json(backend) => backend.store_drafts(drafts).await,
+            Self::Sqlite(backend) => backend.store_drafts(drafts).await,
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::storage::{Talk, UserId};
+
+pub const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// The given hour after the UNIX epoch.
+pub fn hour(hour: u32) -> SystemTime {
+    UNIX_EPOCH + HOUR * hour
+}
+
+/// A talk of one hour, starting at the given hour if it is scheduled.
+pub fn talk(id: usize, start_hour: Option<u32>, location: Option<usize>, nerds: &[UserId]) -> Talk {
+    Talk {
+        id,
+        creator: 0,
+        title: format!("Talk {id}"),
+        description: String::new(),
+        highlight: false,
+        scheduled_at: start_hour.map(hour),
+        duration: HOUR,
+        location,
+        nerds: nerds.iter().copied().collect(),
+        noobs: BTreeSet::new(),
+    }
+}
+
+pub fn talks(talks: impl IntoIterator<Item = Talk>) -> BTreeMap<usize, Talk> {
+    talks.into_iter().map(|talk| (talk.id, talk)).collect()
+}
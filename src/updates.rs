@@ -0,0 +1,172 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::messages::Update;
+
+/// An `Update` together with its position in the stream of all updates.
+#[derive(Clone, Debug, Serialize)]
+pub struct SequencedUpdate<U = Update> {
+    pub seq: u64,
+    #[serde(flatten)]
+    pub update: U,
+}
+
+/// Broadcasts updates to all connections and keeps the most recent ones for resuming clients.
+///
+/// Sequence numbers start at the server's start time in microseconds, so they keep increasing
+/// across restarts and sequence numbers of a previous run are never mistaken for current ones.
+#[derive(Clone, Debug)]
+pub struct UpdatesSender {
+    sender: broadcast::Sender<SequencedUpdate>,
+    replay: Arc<Mutex<Replay>>,
+}
+
+#[derive(Debug)]
+struct Replay {
+    seq: u64,
+    buffer: VecDeque<SequencedUpdate>,
+    capacity: usize,
+}
+
+impl UpdatesSender {
+    pub fn new(channel_capacity: usize, replay_capacity: usize) -> Self {
+        let (sender, _receiver) = broadcast::channel(channel_capacity);
+        let seq = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        Self {
+            sender,
+            replay: Arc::new(Mutex::new(Replay {
+                seq,
+                buffer: VecDeque::with_capacity(replay_capacity),
+                capacity: replay_capacity,
+            })),
+        }
+    }
+
+    /// Assigns the next sequence number to the update and broadcasts it, returning the number.
+    pub fn send(&self, update: Update) -> u64 {
+        let mut replay = self.replay.lock().unwrap();
+        replay.seq += 1;
+        let update = SequencedUpdate {
+            seq: replay.seq,
+            update,
+        };
+        if replay.buffer.len() == replay.capacity {
+            replay.buffer.pop_front();
+        }
+        if replay.capacity > 0 {
+            replay.buffer.push_back(update.clone());
+        }
+        let _ = self.sender.send(update);
+        replay.seq
+    }
+
+    /// Subscribes to all updates after the current one, whose sequence number is returned.
+    pub fn subscribe(&self) -> (u64, broadcast::Receiver<SequencedUpdate>) {
+        let replay = self.replay.lock().unwrap();
+        (replay.seq, self.sender.subscribe())
+    }
+
+    /// Subscribes to all updates after `last_seq`, returning the ones already sent since then.
+    ///
+    /// Returns no missed updates if some of them are no longer buffered, the client then needs a
+    /// full resync.
+    pub fn subscribe_after(
+        &self,
+        last_seq: u64,
+    ) -> (
+        Option<Vec<SequencedUpdate>>,
+        broadcast::Receiver<SequencedUpdate>,
+    ) {
+        let replay = self.replay.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        if last_seq > replay.seq {
+            return (None, receiver);
+        }
+        let oldest_buffered_seq = replay
+            .buffer
+            .front()
+            .map_or(replay.seq + 1, |update| update.seq);
+        if last_seq + 1 < oldest_buffered_seq {
+            return (None, receiver);
+        }
+        let missed_updates = replay
+            .buffer
+            .iter()
+            .filter(|update| update.seq > last_seq)
+            .cloned()
+            .collect();
+        (Some(missed_updates), receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn removed_talk_ids(updates: &[SequencedUpdate]) -> Vec<usize> {
+        updates
+            .iter()
+            .map(|update| match update.update {
+                Update::RemoveTalk { talk_id } => talk_id,
+                _ => panic!("unexpected update {update:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resuming_without_gap_returns_missed_updates() {
+        let sender = UpdatesSender::new(16, 16);
+        let (last_seq, _receiver) = sender.subscribe();
+        sender.send(Update::RemoveTalk { talk_id: 1 });
+        let second_seq = sender.send(Update::RemoveTalk { talk_id: 2 });
+
+        let (missed_updates, mut receiver) = sender.subscribe_after(last_seq);
+        let missed_updates = missed_updates.unwrap();
+        assert_eq!(removed_talk_ids(&missed_updates), [1, 2]);
+        assert_eq!(missed_updates.last().unwrap().seq, second_seq);
+
+        let third_seq = sender.send(Update::RemoveTalk { talk_id: 3 });
+        assert_eq!(receiver.try_recv().unwrap().seq, third_seq);
+    }
+
+    #[test]
+    fn resuming_up_to_date_returns_no_updates() {
+        let sender = UpdatesSender::new(16, 16);
+        let last_seq = sender.send(Update::RemoveTalk { talk_id: 1 });
+
+        let (missed_updates, _receiver) = sender.subscribe_after(last_seq);
+        assert!(missed_updates.unwrap().is_empty());
+    }
+
+    #[test]
+    fn resuming_after_gap_requires_resync() {
+        let sender = UpdatesSender::new(16, 2);
+        let (last_seq, _receiver) = sender.subscribe();
+        for talk_id in 1..=3 {
+            sender.send(Update::RemoveTalk { talk_id });
+        }
+
+        let (missed_updates, _receiver) = sender.subscribe_after(last_seq);
+        assert!(missed_updates.is_none());
+        let (missed_updates, _receiver) = sender.subscribe_after(last_seq + 1);
+        assert_eq!(removed_talk_ids(&missed_updates.unwrap()), [2, 3]);
+    }
+
+    #[test]
+    fn resuming_from_unknown_seq_requires_resync() {
+        let sender = UpdatesSender::new(16, 16);
+        let last_seq = sender.send(Update::RemoveTalk { talk_id: 1 });
+
+        let (missed_updates, _receiver) = sender.subscribe_after(last_seq + 1);
+        assert!(missed_updates.is_none());
+    }
+}